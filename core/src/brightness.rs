@@ -1,49 +1,269 @@
 //! Generic brightness controls.
 
-use std::{
-    fs::{self, File},
-    io::{self, Write},
-    path::Path,
-};
-
-pub fn set_brightness(brightness: u8) -> Result<(), io::Error> {
-    // Attempt to search for a brightness controller
-    let path = Path::new("/sys/class/backlight/");
-
-    if path.exists() {
-        let path_contents = fs::read_dir(path)?;
-        for child in path_contents {
-            let child = child?;
-            let child_metadata = child.metadata()?;
-
-            if child_metadata.is_dir() || child_metadata.is_symlink() {
-                // This is a possibility!
-                let mut child_path = child.path();
-                debug!("Found screen controller classed object: {:?}", child_path);
-                child_path.push("brightness");
-
-                // Get the absolute path
-                let brightness_path = fs::canonicalize(child_path)?;
-                debug!("Choosing brightness object: {:?}", brightness_path);
-
-                if brightness_path.exists() {
-                    let mut file = File::create(brightness_path)?;
-
-                    file.write(format!("{}", brightness).as_bytes())?;
-
-                    info!("Successfully set brightness to {}", brightness);
-
-                    return Ok(());
-                } else {
-                    warn!("Endpoint is missing brightness endpoint")
-                }
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where backlight devices are listed.
+pub static BACKLIGHT_CLASS: &str = "/sys/class/backlight";
+
+/// Device types in the order they should be preferred, as recommended by the kernel:
+/// firmware interfaces know the most about the panel, raw registers the least.
+static TYPE_PREFERENCE: &[&str] = &["firmware", "platform", "raw"];
+
+/// Something which controls how bright the display is.
+pub trait BrightnessControl {
+    /// Name of the device, for logging.
+    fn name(&self) -> String;
+
+    /// Sets the brightness, as a percentage of the maximum.
+    fn set_percent(&mut self, percent: u8) -> io::Result<()>;
+
+    /// Returns the brightness, as a percentage of the maximum.
+    fn get_percent(&mut self) -> io::Result<u8>;
+
+    /// Turns the panel on or off, keeping the brightness level.
+    fn set_power(&mut self, on: bool) -> io::Result<()>;
+}
+
+/// A backlight exposed through sysfs, controlled in percent.
+#[derive(Debug)]
+pub struct Backlight {
+    path: PathBuf,
+    max_brightness: u32,
+}
+
+impl Backlight {
+    /// Opens a backlight device directory, e.g. `/sys/class/backlight/intel_backlight`.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let max_brightness = read_value(&path.join("max_brightness"))?;
+
+        if max_brightness == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Backlight {:?} has a max_brightness of 0", path),
+            ));
+        }
+
+        Ok(Backlight {
+            path: path.to_owned(),
+            max_brightness,
+        })
+    }
+
+    /// Opens the named device in a backlight class directory (normally
+    /// `BACKLIGHT_CLASS`). Without a name, picks the most suitable device.
+    pub fn find(class: &Path, name: Option<&str>) -> io::Result<Self> {
+        if let Some(name) = name {
+            return Self::open(&class.join(name));
+        }
+
+        let mut devices = fs::read_dir(class)?
+            .map(|x| x.map(|x| x.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        devices.sort();
+
+        let preference = |path: &PathBuf| {
+            let kind = fs::read_to_string(path.join("type")).unwrap_or_default();
+            TYPE_PREFERENCE
+                .iter()
+                .position(|x| *x == kind.trim())
+                .unwrap_or(TYPE_PREFERENCE.len())
+        };
+        devices.sort_by_key(preference);
+
+        if devices.len() > 1 {
+            info!(
+                "Found several backlights, choosing the first of {:?}. Set a device name to use another.",
+                devices
+            );
+        }
+
+        for device in &devices {
+            match Self::open(device) {
+                Ok(backlight) => return Ok(backlight),
+                Err(e) => warn!("Ignoring backlight {:?}: {:?}", device, e),
             }
         }
-    } else {
-        warn!("Platform doesn't support Linux-style /sys endpoints");
+
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No usable backlight in {:?}", class),
+        ))
+    }
+
+    fn to_raw(&self, percent: u8) -> u32 {
+        let percent = percent.min(100) as u64;
+        let value = (percent * self.max_brightness as u64 + 50) / 100;
+
+        // Rounding down to 0 would turn the panel off entirely
+        if percent > 0 {
+            value.max(1) as u32
+        } else {
+            0
+        }
+    }
+
+    fn to_percent(&self, value: u32) -> u8 {
+        let value = value.min(self.max_brightness) as u64;
+        ((value * 100 + self.max_brightness as u64 / 2) / self.max_brightness as u64) as u8
+    }
+}
+
+impl BrightnessControl for Backlight {
+    /// Name of the device, e.g. "intel_backlight".
+    fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn set_percent(&mut self, percent: u8) -> io::Result<()> {
+        let value = self.to_raw(percent);
+
+        fs::write(self.path.join("brightness"), value.to_string())?;
+
+        debug!(
+            "Set {} brightness to {}% ({}/{})",
+            self.name(),
+            percent,
+            value,
+            self.max_brightness
+        );
+
+        Ok(())
+    }
+
+    /// Turns the panel on or off through `bl_power`.
+    fn set_power(&mut self, on: bool) -> io::Result<()> {
+        // FB_BLANK_UNBLANK and FB_BLANK_POWERDOWN
+        let value = if on { "0" } else { "4" };
+        fs::write(self.path.join("bl_power"), value)?;
+
+        debug!("Turned {} {}", self.name(), if on { "on" } else { "off" });
+
+        Ok(())
+    }
+
+    /// Returns the brightness as the hardware reports it, as a percentage of the
+    /// maximum. Falls back to the requested brightness if the actual level isn't
+    /// available.
+    fn get_percent(&mut self) -> io::Result<u8> {
+        let value = read_value(&self.path.join("actual_brightness"))
+            .or_else(|_| read_value(&self.path.join("brightness")))?;
+
+        Ok(self.to_percent(value))
+    }
+}
+
+fn read_value(path: &Path) -> io::Result<u32> {
+    fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    fn add_device(class: &Path, name: &str, kind: &str, max: u32) -> PathBuf {
+        let dir = class.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("type"), format!("{}\n", kind)).unwrap();
+        fs::write(dir.join("max_brightness"), format!("{}\n", max)).unwrap();
+        fs::write(dir.join("brightness"), "0\n").unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn scales_to_max_brightness() {
+        let sysfs = tempdir().unwrap();
+        let class = sysfs.path();
+        let dir = add_device(class, "panel", "raw", 4095);
+        let mut backlight = Backlight::find(class, Some("panel")).unwrap();
+
+        backlight.set_percent(100).unwrap();
+        assert_eq!(read(&dir.join("brightness")), "4095");
+        backlight.set_percent(12).unwrap();
+        assert_eq!(read(&dir.join("brightness")), "491");
+        backlight.set_percent(0).unwrap();
+        assert_eq!(read(&dir.join("brightness")), "0");
+
+        // Small panels don't turn off at low levels
+        let dir = add_device(class, "small", "raw", 7);
+        let mut backlight = Backlight::open(&dir).unwrap();
+        backlight.set_percent(5).unwrap();
+        assert_eq!(read(&dir.join("brightness")), "1");
+    }
+
+    #[test]
+    fn reads_actual_brightness() {
+        let sysfs = tempdir().unwrap();
+        let class = sysfs.path();
+        let dir = add_device(class, "panel", "raw", 31);
+        let mut backlight = Backlight::open(&dir).unwrap();
+
+        backlight.set_percent(50).unwrap();
+        assert_eq!(backlight.get_percent().unwrap(), 52);
+
+        // The hardware may not have caught up
+        fs::write(dir.join("actual_brightness"), "31\n").unwrap();
+        assert_eq!(backlight.get_percent().unwrap(), 100);
     }
 
-    warn!("No brightness control available for this platform");
+    #[test]
+    fn switches_power() {
+        let sysfs = tempdir().unwrap();
+        let class = sysfs.path();
+        let dir = add_device(class, "panel", "raw", 255);
+        let mut backlight = Backlight::open(&dir).unwrap();
 
-    Ok(())
+        backlight.set_percent(40).unwrap();
+        backlight.set_power(false).unwrap();
+        assert_eq!(read(&dir.join("bl_power")), "4");
+        backlight.set_power(true).unwrap();
+        assert_eq!(read(&dir.join("bl_power")), "0");
+        assert_eq!(backlight.get_percent().unwrap(), 40);
+    }
+
+    #[test]
+    fn prefers_firmware_devices() {
+        let sysfs = tempdir().unwrap();
+        let class = sysfs.path();
+        add_device(class, "acpi_video0", "firmware", 100);
+        add_device(class, "amdgpu_bl0", "raw", 255);
+        add_device(class, "broken", "firmware", 0);
+
+        assert_eq!(Backlight::find(class, None).unwrap().name(), "acpi_video0");
+        assert_eq!(
+            Backlight::find(class, Some("amdgpu_bl0")).unwrap().name(),
+            "amdgpu_bl0"
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let sysfs = tempdir().unwrap();
+        let class = sysfs.path();
+
+        assert_eq!(
+            Backlight::find(class, None).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert!(Backlight::find(class, Some("missing")).is_err());
+        assert!(Backlight::find(&class.join("missing"), None).is_err());
+
+        let dir = add_device(class, "panel", "raw", 100);
+        let mut backlight = Backlight::open(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(backlight.set_percent(50).is_err());
+    }
 }
@@ -0,0 +1,527 @@
+//! Brightness control for external monitors through DDC/CI, over I2C.
+
+use crate::brightness::BrightnessControl;
+
+#[cfg(target_os = "linux")]
+use i2cdev::core::I2CDevice;
+#[cfg(target_os = "linux")]
+use i2cdev::linux::LinuxI2CDevice;
+
+#[cfg(target_os = "linux")]
+use std::fs;
+use std::io;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Where I2C buses are listed.
+pub static I2C_DEVICES: &str = "/dev";
+
+/// I2C address monitors answer DDC/CI on.
+static DDC_ADDRESS: u16 = 0x37;
+/// Source address of the host, as sent in packets.
+static HOST_ADDRESS: u8 = 0x51;
+/// Address replies are checksummed against.
+static REPLY_ADDRESS: u8 = 0x50;
+
+static GET_VCP: u8 = 0x01;
+static GET_VCP_REPLY: u8 = 0x02;
+static SET_VCP: u8 = 0x03;
+
+/// VCP code for luminance.
+static VCP_BRIGHTNESS: u8 = 0x10;
+/// VCP code for the power mode. 1 is on, 4 is off as if by the power button.
+static VCP_POWER_MODE: u8 = 0xD6;
+
+/// How long monitors need between commands, and before answering a request.
+static COMMAND_DELAY: Duration = Duration::from_millis(50);
+
+/// Raw access to the I2C bus a monitor is on, so DDC/CI can be used without one.
+pub trait I2cTransport {
+    fn write(&mut self, data: &[u8]) -> io::Result<()>;
+    fn read(&mut self, data: &mut [u8]) -> io::Result<()>;
+}
+
+#[cfg(target_os = "linux")]
+impl I2cTransport for LinuxI2CDevice {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        I2CDevice::write(self, data).map_err(io::Error::from)
+    }
+
+    fn read(&mut self, data: &mut [u8]) -> io::Result<()> {
+        I2CDevice::read(self, data).map_err(io::Error::from)
+    }
+}
+
+impl<T: I2cTransport + ?Sized> I2cTransport for &mut T {
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        (**self).write(data)
+    }
+
+    fn read(&mut self, data: &mut [u8]) -> io::Result<()> {
+        (**self).read(data)
+    }
+}
+
+/// A monitor whose brightness is set through DDC/CI.
+pub struct Ddc<T: I2cTransport> {
+    transport: T,
+    name: String,
+    max_brightness: u16,
+    last_command: Option<Instant>,
+    delay: Duration,
+}
+
+#[cfg(target_os = "linux")]
+impl Ddc<LinuxI2CDevice> {
+    /// Opens the monitor on an I2C bus, e.g. `/dev/i2c-2`.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let device = LinuxI2CDevice::new(path, DDC_ADDRESS)?;
+        Self::new(device, path.display().to_string())
+    }
+
+    /// Searches a directory (normally `I2C_DEVICES`) for the first I2C bus with a
+    /// monitor that answers.
+    pub fn find(devices: &Path) -> io::Result<Self> {
+        let mut buses = fs::read_dir(devices)?
+            .map(|x| x.map(|x| x.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        buses.retain(|x| {
+            x.file_name()
+                .and_then(|x| x.to_str())
+                .is_some_and(|x| x.starts_with("i2c-"))
+        });
+        buses.sort();
+
+        for bus in &buses {
+            match Self::open(bus) {
+                Ok(ddc) => return Ok(ddc),
+                Err(e) => debug!("No DDC/CI monitor on {:?}: {:?}", bus, e),
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No monitor answered DDC/CI in {:?}", devices),
+        ))
+    }
+}
+
+impl<T: I2cTransport> Ddc<T> {
+    /// Talks to a monitor over the given transport, checking that its brightness
+    /// can be controlled.
+    pub fn new(transport: T, name: String) -> io::Result<Self> {
+        let mut ddc = Ddc {
+            transport,
+            name,
+            max_brightness: 0,
+            last_command: None,
+            delay: COMMAND_DELAY,
+        };
+
+        let (_, max_brightness) = ddc.get_vcp(VCP_BRIGHTNESS)?;
+        if max_brightness == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Monitor on {} has a maximum brightness of 0", ddc.name),
+            ));
+        }
+        ddc.max_brightness = max_brightness;
+
+        Ok(ddc)
+    }
+
+    /// Reads a VCP feature, returning its current and maximum values.
+    pub fn get_vcp(&mut self, code: u8) -> io::Result<(u16, u16)> {
+        self.send(&[GET_VCP, code])?;
+
+        // Give the monitor time to prepare its answer
+        thread::sleep(self.delay);
+
+        let mut reply = [0; 11];
+        let result = self.transport.read(&mut reply);
+        self.last_command = Some(Instant::now());
+        result?;
+
+        parse_reply(&reply, code)
+    }
+
+    /// Sets a VCP feature.
+    pub fn set_vcp(&mut self, code: u8, value: u16) -> io::Result<()> {
+        let [high, low] = value.to_be_bytes();
+        self.send(&[SET_VCP, code, high, low])
+    }
+
+    fn send(&mut self, payload: &[u8]) -> io::Result<()> {
+        // Commands sent too close together are dropped
+        if let Some(last) = self.last_command {
+            let elapsed = last.elapsed();
+            if elapsed < self.delay {
+                thread::sleep(self.delay - elapsed);
+            }
+        }
+
+        let mut packet = vec![HOST_ADDRESS, 0x80 | payload.len() as u8];
+        packet.extend_from_slice(payload);
+        packet.push(checksum((DDC_ADDRESS << 1) as u8, &packet));
+
+        let result = self.transport.write(&packet);
+        self.last_command = Some(Instant::now());
+        result
+    }
+}
+
+impl<T: I2cTransport + Send + 'static> Ddc<T> {
+    /// Moves the monitor to its own thread, so that slow DDC/CI commands don't
+    /// hold up rendering.
+    pub fn spawn(mut self) -> io::Result<DdcWorker> {
+        let name = self.name();
+        let percent = self.get_percent()?;
+
+        let (sender, receiver) = channel();
+        let (error_sender, errors) = channel();
+
+        let thread = thread::spawn(move || {
+            while let Ok(command) = receiver.recv() {
+                let mut pending = vec![command];
+                pending.extend(receiver.try_iter());
+
+                // Fades queue up levels faster than the monitor takes them, so
+                // only the latest is sent
+                let latest = pending
+                    .iter()
+                    .rposition(|x| matches!(x, DdcCommand::Brightness(_)));
+
+                for (i, command) in pending.into_iter().enumerate() {
+                    let result = match command {
+                        DdcCommand::Brightness(_) if Some(i) != latest => continue,
+                        DdcCommand::Brightness(percent) => self.set_percent(percent),
+                        DdcCommand::Power(on) => self.set_power(on),
+                    };
+
+                    if let Err(e) = result {
+                        let _ = error_sender.send(e);
+                    }
+                }
+            }
+        });
+
+        Ok(DdcWorker {
+            name,
+            percent,
+            sender: Some(sender),
+            errors,
+            thread: Some(thread),
+        })
+    }
+}
+
+enum DdcCommand {
+    Brightness(u8),
+    Power(bool),
+}
+
+/// A DDC/CI monitor driven from a background thread. Failures are reported by the
+/// call after the one which caused them.
+pub struct DdcWorker {
+    name: String,
+    percent: u8,
+    sender: Option<Sender<DdcCommand>>,
+    errors: Receiver<io::Error>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DdcWorker {
+    fn send(&mut self, command: DdcCommand) -> io::Result<()> {
+        if let Some(e) = self.errors.try_iter().last() {
+            return Err(e);
+        }
+
+        self.sender
+            .as_ref()
+            .and_then(|x| x.send(command).ok())
+            .ok_or_else(|| io::Error::other(format!("{} has stopped", self.name)))
+    }
+}
+
+impl BrightnessControl for DdcWorker {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn set_percent(&mut self, percent: u8) -> io::Result<()> {
+        self.percent = percent;
+        self.send(DdcCommand::Brightness(percent))
+    }
+
+    fn get_percent(&mut self) -> io::Result<u8> {
+        Ok(self.percent)
+    }
+
+    fn set_power(&mut self, on: bool) -> io::Result<()> {
+        self.send(DdcCommand::Power(on))
+    }
+}
+
+impl Drop for DdcWorker {
+    /// Waits for anything still queued, e.g. turning the monitor back on at exit.
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<T: I2cTransport> BrightnessControl for Ddc<T> {
+    fn name(&self) -> String {
+        format!("DDC/CI monitor on {}", self.name)
+    }
+
+    fn set_percent(&mut self, percent: u8) -> io::Result<()> {
+        let max = self.max_brightness as u32;
+        let value = (percent.min(100) as u32 * max + 50) / 100;
+
+        self.set_vcp(VCP_BRIGHTNESS, value as u16)?;
+
+        debug!(
+            "Set {} brightness to {}% ({}/{})",
+            self.name(),
+            percent,
+            value,
+            max
+        );
+
+        Ok(())
+    }
+
+    fn get_percent(&mut self) -> io::Result<u8> {
+        let (value, max) = self.get_vcp(VCP_BRIGHTNESS)?;
+        let (value, max) = (value.min(max) as u32, max.max(1) as u32);
+
+        Ok(((value * 100 + max / 2) / max) as u8)
+    }
+
+    fn set_power(&mut self, on: bool) -> io::Result<()> {
+        self.set_vcp(VCP_POWER_MODE, if on { 1 } else { 4 })
+    }
+}
+
+fn checksum(initial: u8, data: &[u8]) -> u8 {
+    data.iter().fold(initial, |acc, x| acc ^ x)
+}
+
+/// Reads a Get VCP Feature reply: source address, length, opcode, result, VCP
+/// code, type, maximum, current value and a checksum.
+fn parse_reply(reply: &[u8; 11], code: u8) -> io::Result<(u16, u16)> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let length = (reply[1] & 0x7F) as usize;
+    if length != 8 {
+        return Err(invalid(format!(
+            "Expected a DDC/CI reply of 8 bytes, got {}",
+            length
+        )));
+    }
+
+    if checksum(REPLY_ADDRESS, &reply[..10]) != reply[10] {
+        return Err(invalid("DDC/CI reply has a bad checksum".to_string()));
+    }
+
+    if reply[2] != GET_VCP_REPLY || reply[4] != code {
+        return Err(invalid(format!(
+            "Expected a reply for VCP code {:#04x}, got {:02x?}",
+            code, reply
+        )));
+    }
+
+    if reply[3] != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Monitor doesn't support VCP code {:#04x}", code),
+        ));
+    }
+
+    let max = u16::from_be_bytes([reply[6], reply[7]]);
+    let current = u16::from_be_bytes([reply[8], reply[9]]);
+
+    Ok((current, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// Answers DDC/CI like a monitor would.
+    #[derive(Default)]
+    struct FakeMonitor {
+        /// VCP codes the monitor supports, with their current and maximum values.
+        features: HashMap<u8, (u16, u16)>,
+        written: Vec<Vec<u8>>,
+        requested: Option<u8>,
+        corrupt: bool,
+    }
+
+    impl FakeMonitor {
+        fn new(brightness: u16, max: u16) -> Self {
+            let mut monitor = FakeMonitor::default();
+            monitor.features.insert(VCP_BRIGHTNESS, (brightness, max));
+            monitor
+        }
+    }
+
+    impl I2cTransport for FakeMonitor {
+        fn write(&mut self, data: &[u8]) -> io::Result<()> {
+            assert_eq!(
+                checksum(0x6E, &data[..data.len() - 1]),
+                data[data.len() - 1]
+            );
+            self.written.push(data.to_vec());
+
+            match data[2] {
+                x if x == GET_VCP => self.requested = Some(data[3]),
+                x if x == SET_VCP => {
+                    let value = u16::from_be_bytes([data[4], data[5]]);
+                    if let Some(feature) = self.features.get_mut(&data[3]) {
+                        feature.0 = value;
+                    }
+                }
+                _ => panic!("Unexpected opcode in {:02x?}", data),
+            }
+
+            Ok(())
+        }
+
+        fn read(&mut self, data: &mut [u8]) -> io::Result<()> {
+            let code = self.requested.take().expect("Read without a request");
+            let (result, (current, max)) = match self.features.get(&code) {
+                Some(feature) => (0, *feature),
+                None => (1, (0, 0)),
+            };
+
+            let [max_high, max_low] = max.to_be_bytes();
+            let [high, low] = current.to_be_bytes();
+            let mut reply = vec![
+                0x6E,
+                0x88,
+                GET_VCP_REPLY,
+                result,
+                code,
+                0,
+                max_high,
+                max_low,
+                high,
+                low,
+            ];
+            reply.push(checksum(REPLY_ADDRESS, &reply) ^ self.corrupt as u8);
+
+            data.copy_from_slice(&reply);
+            Ok(())
+        }
+    }
+
+    impl I2cTransport for Arc<Mutex<FakeMonitor>> {
+        fn write(&mut self, data: &[u8]) -> io::Result<()> {
+            self.lock().unwrap().write(data)
+        }
+
+        fn read(&mut self, data: &mut [u8]) -> io::Result<()> {
+            self.lock().unwrap().read(data)
+        }
+    }
+
+    fn open(monitor: &mut FakeMonitor) -> io::Result<Ddc<&mut FakeMonitor>> {
+        let mut ddc = Ddc::new(monitor, "fake".to_string())?;
+        ddc.delay = Duration::from_millis(0);
+        Ok(ddc)
+    }
+
+    #[test]
+    fn sets_brightness() {
+        let mut monitor = FakeMonitor::new(80, 100);
+        monitor.features.insert(VCP_POWER_MODE, (1, 5));
+
+        {
+            let mut ddc = open(&mut monitor).unwrap();
+            assert_eq!(ddc.get_percent().unwrap(), 80);
+            ddc.set_percent(50).unwrap();
+            assert_eq!(ddc.get_percent().unwrap(), 50);
+            ddc.set_power(false).unwrap();
+        }
+
+        assert_eq!(monitor.written[0], vec![0x51, 0x82, 0x01, 0x10, 0xAC]);
+        assert_eq!(
+            monitor.written[2],
+            vec![0x51, 0x84, 0x03, 0x10, 0x00, 0x32, 0x9A]
+        );
+        assert_eq!(monitor.features[&VCP_POWER_MODE].0, 4);
+    }
+
+    #[test]
+    fn scales_to_max_brightness() {
+        let mut monitor = FakeMonitor::new(0, 255);
+
+        {
+            let mut ddc = open(&mut monitor).unwrap();
+            ddc.set_percent(40).unwrap();
+            assert_eq!(ddc.get_percent().unwrap(), 40);
+        }
+
+        assert_eq!(monitor.features[&VCP_BRIGHTNESS].0, 102);
+    }
+
+    #[test]
+    fn rejects_bad_replies() {
+        let mut monitor = FakeMonitor::new(10, 100);
+        monitor.corrupt = true;
+        assert_eq!(
+            open(&mut monitor).err().unwrap().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let mut monitor = FakeMonitor::default();
+        assert_eq!(
+            open(&mut monitor).err().unwrap().kind(),
+            io::ErrorKind::Unsupported
+        );
+
+        let mut monitor = FakeMonitor::new(0, 0);
+        assert!(open(&mut monitor).is_err());
+    }
+
+    #[test]
+    fn sends_latest_level_from_worker() {
+        let mut monitor = FakeMonitor::new(80, 100);
+        monitor.features.insert(VCP_POWER_MODE, (1, 5));
+        let monitor = Arc::new(Mutex::new(monitor));
+
+        {
+            let mut ddc = Ddc::new(monitor.clone(), "fake".to_string()).unwrap();
+            ddc.delay = Duration::from_millis(20);
+            let mut worker = ddc.spawn().unwrap();
+            assert_eq!(worker.get_percent().unwrap(), 80);
+
+            // Queued faster than the monitor can take them
+            for percent in (0..=50).rev() {
+                worker.set_percent(percent).unwrap();
+            }
+            worker.set_power(false).unwrap();
+            assert_eq!(worker.get_percent().unwrap(), 0);
+        }
+
+        let monitor = monitor.lock().unwrap();
+        let sets = monitor
+            .written
+            .iter()
+            .filter(|x| x[2] == SET_VCP && x[3] == VCP_BRIGHTNESS)
+            .count();
+        assert!(sets < 51, "{}", sets);
+        assert_eq!(monitor.features[&VCP_BRIGHTNESS].0, 0);
+        assert_eq!(monitor.features[&VCP_POWER_MODE].0, 4);
+    }
+}
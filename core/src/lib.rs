@@ -1,10 +1,20 @@
+#[cfg(target_os = "linux")]
+extern crate evdev;
+#[cfg(target_os = "linux")]
+extern crate gpio_cdev;
+#[cfg(target_os = "linux")]
+extern crate i2cdev;
 extern crate image;
 #[macro_use]
 extern crate log;
 
 pub mod backend;
 pub mod brightness;
+pub mod ddc;
 pub mod input;
+pub mod light;
 pub mod pos;
+#[cfg(target_os = "linux")]
+pub mod presence;
 pub mod render;
 pub mod version;
@@ -0,0 +1,178 @@
+//! Ambient light sensors exposed through the Linux IIO subsystem.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where IIO devices are listed.
+pub static IIO_DEVICES: &str = "/sys/bus/iio/devices";
+
+/// Channels reporting illuminance already converted to lux.
+static PROCESSED_CHANNELS: &[&str] = &["in_illuminance_input", "in_illuminance0_input"];
+
+/// Channels reporting raw readings, which need scaling to get lux.
+static RAW_CHANNELS: &[&str] = &["in_illuminance_raw", "in_illuminance0_raw"];
+
+/// A light sensor, read in lux.
+#[derive(Debug)]
+pub struct LightSensor {
+    path: PathBuf,
+    scale: f32,
+    offset: f32,
+}
+
+impl LightSensor {
+    /// Opens a sensor from either an IIO device directory or a channel file within one.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if path.is_file() {
+            let dir = path.parent().unwrap_or_else(|| Path::new("."));
+            let name = path.file_name().and_then(|x| x.to_str()).unwrap_or("");
+
+            return Ok(match name.strip_suffix("_raw") {
+                Some(channel) => Self::raw(dir, channel, path.to_owned()),
+                None => LightSensor {
+                    path: path.to_owned(),
+                    scale: 1.0,
+                    offset: 0.0,
+                },
+            });
+        }
+
+        for channel in PROCESSED_CHANNELS {
+            let file = path.join(channel);
+            if file.is_file() {
+                return Ok(LightSensor {
+                    path: file,
+                    scale: 1.0,
+                    offset: 0.0,
+                });
+            }
+        }
+
+        for channel in RAW_CHANNELS {
+            let file = path.join(channel);
+            if file.is_file() {
+                let channel = channel.trim_end_matches("_raw");
+                return Ok(Self::raw(path, channel, file));
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No illuminance channel in {:?}", path),
+        ))
+    }
+
+    /// Searches a directory of IIO devices (normally `IIO_DEVICES`) for the first
+    /// one that measures illuminance.
+    pub fn find(devices: &Path) -> io::Result<Option<Self>> {
+        let mut entries = fs::read_dir(devices)?
+            .map(|x| x.map(|x| x.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort();
+
+        for entry in entries {
+            if let Ok(sensor) = Self::open(&entry) {
+                debug!("Found light sensor: {:?}", sensor.path);
+                return Ok(Some(sensor));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Raw channels may have a scale and offset alongside them, or shared by every
+    /// channel on the device.
+    fn raw(dir: &Path, channel: &str, path: PathBuf) -> Self {
+        let read = |suffix: &str, default: f32| {
+            [format!("{}_{}", channel, suffix), format!("in_{}", suffix)]
+                .iter()
+                .find_map(|name| read_value(&dir.join(name)).ok())
+                .unwrap_or(default)
+        };
+
+        LightSensor {
+            scale: read("scale", 1.0),
+            offset: read("offset", 0.0),
+            path,
+        }
+    }
+
+    /// Returns the current illuminance in lux.
+    pub fn read(&self) -> io::Result<f32> {
+        Ok((read_value(&self.path)? + self.offset) * self.scale)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn read_value(path: &Path) -> io::Result<f32> {
+    fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    fn write(dir: &Path, name: &str, contents: &str) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(name), contents).unwrap();
+    }
+
+    #[test]
+    fn reads_processed_channels() {
+        let sysfs = tempdir().unwrap();
+        let root = sysfs.path();
+        let accel = root.join("iio:device0");
+        let light = root.join("iio:device1");
+        write(&accel, "in_accel_x_raw", "12\n");
+        write(&light, "in_illuminance_input", "123.5\n");
+
+        let sensor = LightSensor::find(root)
+            .unwrap()
+            .expect("Sensor should be found");
+        assert_eq!(sensor.path(), light.join("in_illuminance_input"));
+        assert_eq!(sensor.read().unwrap(), 123.5);
+
+        write(&light, "in_illuminance_input", "4\n");
+        assert_eq!(sensor.read().unwrap(), 4.0);
+    }
+
+    #[test]
+    fn scales_raw_channels() {
+        let sysfs = tempdir().unwrap();
+        let root = sysfs.path();
+        let light = root.join("iio:device0");
+        write(&light, "in_illuminance_raw", "200\n");
+        write(&light, "in_illuminance_scale", "0.5\n");
+        write(&light, "in_illuminance_offset", "10\n");
+
+        let sensor = LightSensor::open(&light).unwrap();
+        assert_eq!(sensor.read().unwrap(), 105.0);
+
+        // Opening the channel directly finds the same scale
+        let sensor = LightSensor::open(&light.join("in_illuminance_raw")).unwrap();
+        assert_eq!(sensor.read().unwrap(), 105.0);
+
+        write(&light, "in_illuminance_raw", "garbage");
+        assert!(sensor.read().is_err());
+    }
+
+    #[test]
+    fn handles_missing_sensors() {
+        let sysfs = tempdir().unwrap();
+        let root = sysfs.path();
+        write(&root.join("iio:device0"), "in_voltage0_raw", "1");
+
+        assert!(LightSensor::find(root).unwrap().is_none());
+        assert!(LightSensor::open(&root.join("iio:device0")).is_err());
+        assert!(LightSensor::find(&root.join("nothing")).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Presence sensors, like PIR motion detectors, which tell when someone is around.
+
+use evdev::EventType;
+use gpio_cdev::{Chip, EventRequestFlags, LineRequestFlags};
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Where presence is read from.
+#[derive(Clone, Debug)]
+pub enum PresenceSource {
+    /// A GPIO line, e.g. the output of a PIR sensor, through its character device.
+    Gpio {
+        chip: PathBuf,
+        line: u32,
+        /// The line reads low while someone is present.
+        active_low: bool,
+    },
+    /// A key or switch on an evdev device, e.g. a proximity switch. Any key or
+    /// switch counts if no code is given.
+    Evdev { device: PathBuf, code: Option<u16> },
+}
+
+/// Follows a presence sensor, which is read in the background.
+pub struct PresenceSensor {
+    receiver: Receiver<bool>,
+    present: bool,
+    /// When someone was last seen, if they have been at all.
+    last_seen: Option<Instant>,
+    failed: bool,
+}
+
+impl PresenceSensor {
+    pub fn open(source: &PresenceSource) -> io::Result<Self> {
+        let (sender, receiver) = channel();
+
+        // Someone already present when the sensor is opened hasn't arrived
+        let mut initial = false;
+
+        match source {
+            PresenceSource::Gpio {
+                chip,
+                line,
+                active_low,
+            } => {
+                let to_io = |e: gpio_cdev::Error| io::Error::other(e.to_string());
+
+                let line = Chip::new(chip)
+                    .map_err(to_io)?
+                    .get_line(*line)
+                    .map_err(to_io)?;
+                let events = line
+                    .events(
+                        LineRequestFlags::INPUT,
+                        EventRequestFlags::BOTH_EDGES,
+                        "leaffront",
+                    )
+                    .map_err(to_io)?;
+
+                let active_low = *active_low;
+                initial = (events.get_value().map_err(to_io)? != 0) != active_low;
+
+                thread::spawn(move || {
+                    for event in events {
+                        let present = match event {
+                            Ok(event) => {
+                                (event.event_type() == gpio_cdev::EventType::RisingEdge)
+                                    != active_low
+                            }
+                            Err(e) => {
+                                warn!("Failed to read presence from GPIO: {}", e);
+                                break;
+                            }
+                        };
+
+                        if sender.send(present).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            PresenceSource::Evdev { device, code } => {
+                let mut device = evdev::Device::open(device)?;
+                let code = *code;
+
+                thread::spawn(move || watch_evdev(&mut device, code, sender));
+            }
+        }
+
+        Ok(Self::with_initial(receiver, initial))
+    }
+
+    /// Follows changes in presence sent from elsewhere.
+    pub fn from_receiver(receiver: Receiver<bool>) -> Self {
+        Self::with_initial(receiver, false)
+    }
+
+    fn with_initial(receiver: Receiver<bool>, present: bool) -> Self {
+        PresenceSensor {
+            receiver,
+            present,
+            last_seen: present.then(Instant::now),
+            failed: false,
+        }
+    }
+
+    /// Takes in any changes from the sensor. Returns true if someone has arrived
+    /// since the last call.
+    pub fn update(&mut self, now: Instant) -> bool {
+        let mut arrived = false;
+
+        loop {
+            match self.receiver.try_recv() {
+                Ok(present) => {
+                    arrived |= present && !self.present;
+                    if present || self.present {
+                        self.last_seen = Some(now);
+                    }
+                    self.present = present;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if !self.failed {
+                        warn!("Presence sensor stopped, treating the room as empty");
+                        self.failed = true;
+                    }
+                    self.present = false;
+                    break;
+                }
+            }
+        }
+
+        if self.present {
+            self.last_seen = Some(now);
+        }
+
+        arrived
+    }
+
+    /// Returns how long it has been since anyone was seen. The room counts as
+    /// long empty if nobody has been seen since the sensor was opened.
+    pub fn idle_for(&self, now: Instant) -> Duration {
+        self.last_seen
+            .map_or(Duration::MAX, |x| now.saturating_duration_since(x))
+    }
+}
+
+fn watch_evdev(device: &mut evdev::Device, code: Option<u16>, sender: Sender<bool>) {
+    let name = device.name().map(|x| x.to_owned());
+
+    loop {
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("Failed to read presence from {:?}: {:?}", name, e);
+                return;
+            }
+        };
+
+        for event in events {
+            let kind = event.event_type();
+            if kind != EventType::KEY && kind != EventType::SWITCH {
+                continue;
+            }
+            if code.is_some_and(|x| x != event.code()) {
+                continue;
+            }
+
+            // Key repeats (2) still mean someone is there
+            if sender.send(event.value() != 0).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_arrivals() {
+        let (sender, receiver) = channel();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let mut sensor = PresenceSensor::from_receiver(receiver);
+
+        // Nobody seen yet, so already idle
+        assert!(!sensor.update(at(0)));
+        assert_eq!(sensor.idle_for(at(0)), Duration::MAX);
+
+        // Reports of nobody being there don't count as a sighting
+        sender.send(false).unwrap();
+        assert!(!sensor.update(at(5)));
+        assert_eq!(sensor.idle_for(at(5)), Duration::MAX);
+
+        sender.send(true).unwrap();
+        assert!(sensor.update(at(10)));
+        assert!(!sensor.update(at(20)));
+
+        // Still there, so never idle
+        assert_eq!(sensor.idle_for(at(20)), Duration::from_secs(0));
+
+        // Idle from when they left
+        sender.send(false).unwrap();
+        assert!(!sensor.update(at(30)));
+        assert_eq!(sensor.idle_for(at(90)), Duration::from_secs(60));
+
+        // Brief blips still count as arriving
+        sender.send(true).unwrap();
+        sender.send(false).unwrap();
+        assert!(sensor.update(at(100)));
+        assert_eq!(sensor.idle_for(at(100)), Duration::from_secs(0));
+
+        drop(sender);
+        assert!(!sensor.update(at(110)));
+        assert_eq!(sensor.idle_for(at(110)), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn present_when_opened() {
+        let (_sender, receiver) = channel();
+        let start = Instant::now();
+
+        // Someone already there keeps the room busy without having arrived
+        let mut sensor = PresenceSensor::with_initial(receiver, true);
+        assert!(!sensor.update(start));
+        assert_eq!(sensor.idle_for(start), Duration::from_secs(0));
+    }
+}
@@ -23,4 +23,58 @@ impl Color {
     pub fn new_3byte(r: u8, g: u8, b: u8) -> Self {
         Color::new_4byte(r, g, b, 255)
     }
+
+    /// The color of light from a black body at a temperature in kelvin, scaled so
+    /// that 6500K is white. Lower temperatures are warmer.
+    pub fn from_temperature(kelvin: f32) -> Self {
+        let color = black_body(kelvin);
+        let white = black_body(6500.0);
+
+        let channel = |i: usize| (color[i] / white[i] * 255.0).round().clamp(0.0, 255.0) as u8;
+        Color::new_3byte(channel(0), channel(1), channel(2))
+    }
+}
+
+/// Approximates the RGB color of a black body, fitted to the CIE 1964 curve as
+/// per Tanner Helland.
+fn black_body(kelvin: f32) -> [f32; 3] {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+
+    let r = if t <= 66.0 {
+        255.0
+    } else {
+        329.69873 * (t - 60.0).powf(-0.13320476)
+    };
+    let g = if t <= 66.0 {
+        99.4708 * t.ln() - 161.11957
+    } else {
+        288.12216 * (t - 60.0).powf(-0.075514846)
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.51773 * (t - 10.0).ln() - 305.0448
+    };
+
+    [r, g, b].map(|x| x.clamp(0.0, 255.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_temperatures() {
+        let daylight = Color::from_temperature(6500.0);
+        assert!(daylight == Color::new_3byte(255, 255, 255));
+
+        let candle = Color::from_temperature(1900.0);
+        assert_eq!(candle.r, 255);
+        assert!(candle.g < 150 && candle.b == 0);
+
+        let evening = Color::from_temperature(3400.0);
+        assert!(evening.g > candle.g && evening.b > candle.b && evening.b < 255);
+    }
 }
@@ -9,6 +9,8 @@ use crate::render::texture::Texture;
 
 use image::DynamicImage;
 
+use std::io;
+
 /// The dimensions of a object
 pub trait Dimensions {
     /// Returns the width of this object.
@@ -49,12 +51,16 @@ pub trait Drawer {
     /// have ways to accelerate this beyond OpenGL calls.
     fn set_background(&mut self, image: DynamicImage);
 
-    /// Sets the screen brightness, if possible. Ignore call if not.
-    fn set_brightness(&mut self, brightness: u8) -> ::std::io::Result<()>;
-
     /// Configures the full screen state of the window if possible.
     fn set_fullscreen(&mut self, fullscreen: bool);
 
+    /// Turns the display itself on or off, e.g. through DPMS.
+    fn set_display_power(&mut self, on: bool) -> io::Result<()>;
+
+    /// Multiplies everything drawn, including the background, by a color. White
+    /// leaves the frame as it is.
+    fn set_color_filter(&mut self, filter: &Color);
+
     /// Draws a texture to the screen, with a specified set of vertices to draw to, a UV
     /// to decode the image with, and a color to use as a base.
     fn draw_textured_vertices_colored_uv(
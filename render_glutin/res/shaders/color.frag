@@ -1,9 +1,11 @@
 #version 150 core
 
+uniform vec3 color_filter;
+
 in vec4 output_color;
 
 out vec4 outColor;
 
 void main() {
-    outColor = output_color;
+    outColor = vec4(output_color.rgb * color_filter, output_color.a);
 }
@@ -1,6 +1,7 @@
 #version 150 core
 
 uniform sampler2D bind_tex;
+uniform vec3 color_filter;
 
 in vec2 output_uv;
 in vec4 output_color;
@@ -8,5 +9,6 @@ in vec4 output_color;
 out vec4 outColor;
 
 void main() {
-    outColor = texture2D(bind_tex, output_uv) * output_color;
+    vec4 color = texture2D(bind_tex, output_uv) * output_color;
+    outColor = vec4(color.rgb * color_filter, color.a);
 }
\ No newline at end of file
@@ -20,6 +20,7 @@ use glutin::{ContextWrapper, PossiblyCurrent};
 
 use gl;
 
+use std::io;
 use std::ptr;
 
 use std::mem::MaybeUninit;
@@ -54,6 +55,10 @@ pub struct GlutinDrawer {
     uv: GLVBO,
     attr_textured_uv: gl::types::GLint,
 
+    // Color filter, used by both shaders
+    uniform_colored_filter: gl::types::GLint,
+    uniform_textured_filter: gl::types::GLint,
+
     // Background image
     background: Option<GlTexture>,
 
@@ -216,6 +221,7 @@ impl GlutinDrawer {
         colored_shader.use_program();
         let attr_colored_vertex = colored_shader.get_attribute("input_vertex");
         let attr_colored_color = colored_shader.get_attribute("input_color");
+        let uniform_colored_filter = colored_shader.get_uniform("color_filter");
 
         let textured_shader = GLSLShader::create_shader(
             include_bytes!("../res/shaders/tex.vert"),
@@ -227,8 +233,9 @@ impl GlutinDrawer {
         let attr_textured_vertex = textured_shader.get_attribute("input_vertex");
         let attr_textured_color = textured_shader.get_attribute("input_color");
         let attr_textured_uv = textured_shader.get_attribute("input_uv");
+        let uniform_textured_filter = textured_shader.get_uniform("color_filter");
 
-        GlutinDrawer {
+        let mut drawer = GlutinDrawer {
             events_loop: Some(events_loop),
             gl_window,
             colored: colored_shader,
@@ -242,10 +249,16 @@ impl GlutinDrawer {
             attr_textured_color,
             uv: uv_vbo,
             attr_textured_uv,
+            uniform_colored_filter,
+            uniform_textured_filter,
             background: None,
             transition_count: 0,
             calls: 0,
-        }
+        };
+
+        drawer.set_color_filter(&Color::new_3byte(255, 255, 255));
+
+        drawer
     }
 }
 
@@ -365,11 +378,6 @@ impl Drawer for GlutinDrawer {
         self.background = Some(image);
     }
 
-    /// Sets the brightness of the screen.
-    fn set_brightness(&mut self, val: u8) -> ::std::io::Result<()> {
-        leaffront_core::brightness::set_brightness(val)
-    }
-
     fn set_fullscreen(&mut self, fullscreen: bool) {
         self.gl_window.window().set_fullscreen(if fullscreen {
             Some(Fullscreen::Borderless(None))
@@ -379,6 +387,31 @@ impl Drawer for GlutinDrawer {
         self.gl_window.window().set_cursor_visible(!fullscreen)
     }
 
+    fn set_display_power(&mut self, on: bool) -> io::Result<()> {
+        set_dpms(self.gl_window.window(), on)
+    }
+
+    fn set_color_filter(&mut self, filter: &Color) {
+        self.configure_state(DrawState::None);
+
+        let (r, g, b) = (
+            filter.r as f32 / 255.0,
+            filter.g as f32 / 255.0,
+            filter.b as f32 / 255.0,
+        );
+
+        self.colored.use_program();
+        unsafe {
+            gl::Uniform3f(self.uniform_colored_filter, r, g, b);
+        }
+
+        self.textured.use_program();
+        unsafe {
+            gl::Uniform3f(self.uniform_textured_filter, r, g, b);
+            gl::UseProgram(0);
+        }
+    }
+
     fn get_transition_count(&self) -> usize {
         self.transition_count
     }
@@ -439,3 +472,47 @@ unsafe fn cstring_to_string(mut cs: *const c_char) -> String {
     }
     String::from_utf8(v).expect("c-string not utf8")
 }
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set_dpms(window: &glutin::window::Window, on: bool) -> io::Result<()> {
+    use glutin::platform::unix::WindowExtUnix;
+    use x11_dl::dpms::{self, Xext};
+    use x11_dl::xlib::{self, Xlib};
+
+    let unsupported = |msg: &str| io::Error::new(io::ErrorKind::Unsupported, msg.to_string());
+
+    let display = window
+        .xlib_display()
+        .ok_or_else(|| unsupported("DPMS is only available under X11"))?
+        as *mut xlib::Display;
+
+    let xext = Xext::open().map_err(|e| unsupported(&format!("{}", e)))?;
+    let xlib = Xlib::open().map_err(|e| unsupported(&format!("{}", e)))?;
+
+    unsafe {
+        if (xext.DPMSCapable)(display) == 0 {
+            return Err(unsupported("The X server doesn't support DPMS"));
+        }
+
+        (xext.DPMSEnable)(display);
+        (xext.DPMSForceLevel)(
+            display,
+            if on {
+                dpms::DPMSModeOn
+            } else {
+                dpms::DPMSModeOff
+            },
+        );
+        (xlib.XFlush)(display);
+    }
+
+    Ok(())
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn set_dpms(_window: &glutin::window::Window, _on: bool) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Display power can only be controlled through DPMS under X11",
+    ))
+}
@@ -3,6 +3,8 @@ extern crate image;
 
 extern crate gl;
 extern crate libc;
+#[cfg(all(unix, not(target_os = "macos")))]
+extern crate x11_dl;
 
 extern crate leaffront_core;
 
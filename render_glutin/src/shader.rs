@@ -26,6 +26,12 @@ impl GLSLShader {
         unsafe { gl::GetAttribLocation(self.program, string.as_ptr()) }
     }
 
+    /// Returns the location of a uniform in this program.
+    pub fn get_uniform(&self, name: &str) -> gl::types::GLint {
+        let string = CString::new(name).unwrap();
+        unsafe { gl::GetUniformLocation(self.program, string.as_ptr()) }
+    }
+
     /// Creates a new shader.
     /// Returns: Shader if compile succeeded, msg if failed.
     pub fn create_shader(vertex: &[u8], frag: &[u8]) -> Result<GLSLShader, String> {
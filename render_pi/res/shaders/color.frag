@@ -1,5 +1,7 @@
+uniform vec3 color_filter;
+
 varying vec4 output_color;
 
 void main() {
-    gl_FragColor = output_color;
+    gl_FragColor = vec4(output_color.rgb * color_filter, output_color.a);
 }
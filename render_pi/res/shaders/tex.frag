@@ -1,8 +1,10 @@
 uniform sampler2D bind_tex;
+uniform vec3 color_filter;
 
 varying vec2 output_uv;
 varying vec4 output_color;
 
 void main() {
-    gl_FragColor = texture2D(bind_tex, output_uv) * output_color;
+    vec4 color = texture2D(bind_tex, output_uv) * output_color;
+    gl_FragColor = vec4(color.rgb * color_filter, color.a);
 }
\ No newline at end of file
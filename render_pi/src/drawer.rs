@@ -1,7 +1,7 @@
 /// Manages an interface for drawing different kinds of images.
 use opengles::glesv2 as gl;
 
-use image::DynamicImage;
+use image::{DynamicImage, RgbImage};
 
 use videocore::dispmanx;
 use videocore::dispmanx::ResourceHandle;
@@ -15,17 +15,18 @@ use videocore::bcm_host::GraphicsDisplaySize;
 
 use gl_context::Context;
 
-use brightness::set_brightness;
-
 use shader::GLSLShader;
 use texture::GlTexture;
 use vbo::GLVBO;
 
 use leaffront_core::pos::Rect;
+use leaffront_core::render::color::Color;
 use leaffront_core::render::texture::Texture;
 use leaffront_core::render::Drawer;
 use leaffront_core::version::VersionInfo;
 
+use std::io;
+
 #[derive(Ord, PartialOrd, Eq, PartialEq)]
 enum DrawState {
     None,
@@ -55,9 +56,16 @@ pub struct PiDrawer {
     uv: GLVBO,
     attr_textured_uv: gl::GLint,
 
+    // Color filter, used by both shaders
+    uniform_colored_filter: gl::GLint,
+    uniform_textured_filter: gl::GLint,
+    filter: Color,
+
     context: Context,
 
     bg: Option<ResourceHandle>,
+    // Background as given, so it can be filtered again
+    bg_image: Option<RgbImage>,
 
     // Debugging information
     transitions: usize,
@@ -182,6 +190,7 @@ impl PiDrawer {
         colored_shader.use_program();
         let attr_colored_vertex = colored_shader.get_attribute("input_vertex");
         let attr_colored_color = colored_shader.get_attribute("input_color");
+        let uniform_colored_filter = colored_shader.get_uniform("color_filter");
 
         let textured_shader = GLSLShader::create_shader(
             include_bytes!("../res/shaders/tex.vert"),
@@ -193,8 +202,9 @@ impl PiDrawer {
         let attr_textured_vertex = textured_shader.get_attribute("input_vertex");
         let attr_textured_color = textured_shader.get_attribute("input_color");
         let attr_textured_uv = textured_shader.get_attribute("input_uv");
+        let uniform_textured_filter = textured_shader.get_uniform("color_filter");
 
-        Self {
+        let mut drawer = Self {
             context,
             size,
             state: DrawState::None,
@@ -208,9 +218,17 @@ impl PiDrawer {
             attr_textured_color,
             uv: uv_vbo,
             attr_textured_uv,
+            uniform_colored_filter,
+            uniform_textured_filter,
+            filter: Color::new_3byte(255, 255, 255),
             bg: None,
+            bg_image: None,
             transitions: 0,
-        }
+        };
+
+        drawer.set_color_filter(&Color::new_3byte(255, 255, 255));
+
+        drawer
     }
 }
 
@@ -310,7 +328,18 @@ impl Drawer for PiDrawer {
             _ => {}
         }
 
-        let bg_img = image.to_rgb8();
+        let mut bg_img = image.to_rgb8();
+        self.bg_image = Some(bg_img.clone());
+
+        // The background is on its own layer, out of reach of the shaders
+        if self.filter != Color::new_3byte(255, 255, 255) {
+            let filter = [self.filter.r, self.filter.g, self.filter.b];
+            for pixel in bg_img.pixels_mut() {
+                for (channel, filter) in pixel.0.iter_mut().zip(filter) {
+                    *channel = (*channel as u16 * filter as u16 / 255) as u8;
+                }
+            }
+        }
 
         // Resize the background to the correct size
         //let size = Context::get_resolution();
@@ -412,15 +441,42 @@ impl Drawer for PiDrawer {
         self.bg = Some(bg_resource);
     }
 
-    /// Sets the brightness of the screen.
-    fn set_brightness(&mut self, val: u8) -> ::std::io::Result<()> {
-        leaffront_core::brightness::set_brightness(val)
-    }
-
     fn set_fullscreen(&mut self, _fullscreen: bool) {
         // NOOP
     }
 
+    fn set_display_power(&mut self, _on: bool) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Display power can't be controlled through dispmanx; use the backlight or a command",
+        ))
+    }
+
+    fn set_color_filter(&mut self, filter: &Color) {
+        self.configure_state(DrawState::None);
+
+        let (r, g, b) = (
+            filter.r as f32 / 255.0,
+            filter.g as f32 / 255.0,
+            filter.b as f32 / 255.0,
+        );
+
+        self.colored.use_program();
+        gl::uniform3f(self.uniform_colored_filter, r, g, b);
+
+        self.textured.use_program();
+        gl::uniform3f(self.uniform_textured_filter, r, g, b);
+        gl::use_program(0);
+
+        if *filter != self.filter {
+            self.filter = filter.clone();
+
+            if let Some(image) = self.bg_image.take() {
+                self.set_background(DynamicImage::ImageRgb8(image));
+            }
+        }
+    }
+
     fn get_transition_count(&self) -> usize {
         self.transitions
     }
@@ -20,6 +20,11 @@ impl GLSLShader {
         gl::get_attrib_location(self.program, name)
     }
 
+    /// Returns the location of a uniform in this program.
+    pub fn get_uniform(&self, name: &str) -> gl::GLint {
+        gl::get_uniform_location(self.program, name)
+    }
+
     /// Creates a new shader.
     /// Returns: Shader if compile succeeded, msg if failed.
     pub fn create_shader(vertex: &[u8], frag: &[u8]) -> Result<GLSLShader, String> {
@@ -0,0 +1,251 @@
+//! Dims the display in a dark room by following an ambient light sensor.
+
+use leaffront_core::light::LightSensor;
+
+use std::time::{Duration, Instant};
+
+/// How often the sensor is read.
+static POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How lux maps to brightness, and how readily the brightness follows it.
+#[derive(Clone, Debug)]
+pub struct AmbientSettings {
+    /// (lux, brightness) points, sorted by lux. Brightness is interpolated linearly
+    /// between them, and held at the ends.
+    pub curve: Vec<(f32, f32)>,
+    /// Roughly how long the smoothed reading takes to catch up with a change.
+    pub smoothing: Duration,
+    /// How far brightness has to move before it's changed.
+    pub hysteresis: f32,
+    pub min_brightness: f32,
+    pub max_brightness: f32,
+}
+
+impl AmbientSettings {
+    /// Maps an illuminance to a brightness, before clamping.
+    pub fn brightness_for(&self, lux: f32) -> f32 {
+        let (first, last) = match (self.curve.first(), self.curve.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return self.max_brightness,
+        };
+
+        if lux <= first.0 {
+            return first.1;
+        }
+
+        for pair in self.curve.windows(2) {
+            let ((low_lux, low), (high_lux, high)) = (pair[0], pair[1]);
+
+            if lux <= high_lux {
+                // Two points at the same lux have nothing to interpolate between
+                if high_lux <= low_lux {
+                    return high;
+                }
+
+                let progress = (lux - low_lux) / (high_lux - low_lux);
+                return low + (high - low) * progress;
+            }
+        }
+
+        last.1
+    }
+}
+
+/// Follows a light sensor, producing a smoothed brightness level.
+pub struct AmbientLight {
+    sensor: LightSensor,
+    settings: AmbientSettings,
+    /// Smoothed illuminance, and when it was last updated.
+    lux: Option<(f32, Instant)>,
+    brightness: Option<f32>,
+    last_poll: Option<Instant>,
+    failing: bool,
+}
+
+impl AmbientLight {
+    pub fn new(sensor: LightSensor, mut settings: AmbientSettings) -> Self {
+        settings
+            .curve
+            .sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        AmbientLight {
+            sensor,
+            settings,
+            lux: None,
+            brightness: None,
+            last_poll: None,
+            failing: false,
+        }
+    }
+
+    /// Reads the sensor if it's due, returning the brightness the room calls for.
+    /// `None` until the sensor has been read successfully.
+    pub fn update(&mut self, now: Instant) -> Option<f32> {
+        let due = self
+            .last_poll
+            .is_none_or(|last| now.saturating_duration_since(last) >= POLL_INTERVAL);
+
+        if due {
+            self.last_poll = Some(now);
+
+            match self.sensor.read() {
+                Ok(lux) => {
+                    self.failing = false;
+                    self.add_reading(lux, now);
+                }
+                Err(e) => {
+                    // Keep the last level rather than flickering
+                    if !self.failing {
+                        warn!(
+                            "Failed to read light sensor {:?}: {:?}",
+                            self.sensor.path(),
+                            e
+                        );
+                    }
+                    self.failing = true;
+                }
+            }
+        }
+
+        self.brightness
+    }
+
+    fn add_reading(&mut self, lux: f32, now: Instant) {
+        let lux = match self.lux {
+            // Exponential moving average, weighted by the time since the last reading
+            Some((previous, updated)) if !self.settings.smoothing.is_zero() => {
+                let elapsed = now.saturating_duration_since(updated).as_secs_f32();
+                let weight = 1.0 - (-elapsed / self.settings.smoothing.as_secs_f32()).exp();
+                previous + (lux - previous) * weight
+            }
+            _ => lux,
+        };
+        self.lux = Some((lux, now));
+
+        let settings = &self.settings;
+        let target = settings
+            .brightness_for(lux)
+            .clamp(settings.min_brightness, settings.max_brightness);
+
+        // Ignore small changes so the backlight doesn't keep hunting
+        let changed = self
+            .brightness
+            .is_none_or(|current| (target - current).abs() >= settings.hysteresis);
+
+        if changed {
+            debug!("Ambient light is {:.1} lux, brightness {:.0}", lux, target);
+            self.brightness = Some(target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+    use std::path::PathBuf;
+
+    use tempfile::tempdir;
+
+    fn settings() -> AmbientSettings {
+        AmbientSettings {
+            curve: vec![(100.0, 80.0), (0.0, 10.0), (10.0, 30.0)],
+            smoothing: Duration::from_secs(0),
+            hysteresis: 5.0,
+            min_brightness: 15.0,
+            max_brightness: 70.0,
+        }
+    }
+
+    fn set_lux(path: &PathBuf, lux: f32) {
+        fs::write(path, format!("{}\n", lux)).unwrap();
+    }
+
+    #[test]
+    fn follows_curve() {
+        let mut settings = settings();
+        settings
+            .curve
+            .sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        assert_eq!(settings.brightness_for(0.0), 10.0);
+        assert_eq!(settings.brightness_for(5.0), 20.0);
+        assert_eq!(settings.brightness_for(55.0), 55.0);
+        assert_eq!(settings.brightness_for(5000.0), 80.0);
+    }
+
+    #[test]
+    fn handles_duplicate_points() {
+        let mut settings = settings();
+        settings.curve = vec![(0.0, 10.0), (0.0, 20.0), (10.0, 30.0), (10.0, 50.0)];
+
+        for lux in [0.0, 5.0, 10.0, 20.0] {
+            assert!(!settings.brightness_for(lux).is_nan(), "{}", lux);
+        }
+        assert_eq!(settings.brightness_for(5.0), 25.0);
+        assert_eq!(settings.brightness_for(20.0), 50.0);
+
+        // Unsorted curves still don't divide by zero
+        settings.curve = vec![(10.0, 30.0), (10.0, 40.0), (0.0, 10.0)];
+        assert!(!settings.brightness_for(5.0).is_nan());
+    }
+
+    #[test]
+    fn clamps_with_hysteresis() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("in_illuminance_input");
+        set_lux(&path, 0.0);
+
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut ambient = AmbientLight::new(LightSensor::open(&path).unwrap(), settings());
+
+        // Clamped up to the minimum
+        assert_eq!(ambient.update(at(0)), Some(15.0));
+
+        // Not read again until the next poll
+        set_lux(&path, 3.0);
+        assert_eq!(ambient.update(at(0)), Some(15.0));
+
+        // Within the hysteresis
+        assert_eq!(ambient.update(at(1)), Some(15.0));
+
+        set_lux(&path, 10.0);
+        assert_eq!(ambient.update(at(2)), Some(30.0));
+
+        // Clamped down to the maximum
+        set_lux(&path, 1000.0);
+        assert_eq!(ambient.update(at(3)), Some(70.0));
+
+        // Failed readings keep the last level
+        fs::write(&path, "").unwrap();
+        assert_eq!(ambient.update(at(4)), Some(70.0));
+    }
+
+    #[test]
+    fn smooths_readings() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("in_illuminance_input");
+        set_lux(&path, 100.0);
+
+        let mut settings = settings();
+        settings.smoothing = Duration::from_secs(10);
+        settings.hysteresis = 0.0;
+        settings.min_brightness = 0.0;
+        settings.max_brightness = 100.0;
+
+        let start = Instant::now();
+        let mut ambient = AmbientLight::new(LightSensor::open(&path).unwrap(), settings);
+        assert_eq!(ambient.update(start), Some(80.0));
+
+        // A sudden drop only moves part of the way
+        set_lux(&path, 0.0);
+        let level = ambient.update(start + Duration::from_secs(1)).unwrap();
+        assert!(level < 80.0 && level > 30.0, "{}", level);
+
+        // ...but gets there eventually
+        let level = ambient.update(start + Duration::from_secs(120)).unwrap();
+        assert!(level < 10.5, "{}", level);
+    }
+}
@@ -1,33 +1,382 @@
-use chrono::naive::NaiveTime;
-use chrono::Duration as cDuration;
-/// Functions to help with time of day functionality.
-use chrono::Local;
-
-pub fn check_night(start_night: u32, end_night: u32) -> bool {
-    let time = Local::now();
-    let start_time =
-        NaiveTime::from_hms_opt(start_night, 0, 0).expect("Failed to parse start time");
-    let end_time = NaiveTime::from_hms_opt(end_night, 0, 0).expect("Failed to parse end time");
-    let cur_date = time.naive_local();
-    let cur_time = cur_date.time();
-
-    let start_date = if (cur_time < end_time) && !(start_time < end_time) {
-        // Early morning
-        let start_date = time.date_naive();
-        let start_date = start_date - cDuration::days(1);
-        start_date.and_time(start_time)
-    } else {
-        time.date_naive().and_time(start_time)
-    };
-
-    let end_date = if start_time > end_time && !(cur_time < end_time) {
-        // End night is on the next day
-        let end_date = time.date_naive();
-        let end_date = end_date + cDuration::days(1);
-        end_date.and_time(end_time)
+//! Formats the clock, date and other times shown on screen.
+
+use crate::locale::{Locale, Strings};
+use crate::overrides::parse_expiry;
+use crate::schedule::Schedule;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use std::fmt::Display;
+
+/// Which time zone times are shown in.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Zone {
+    /// Whatever the system is set to.
+    Local,
+    Named(Tz),
+}
+
+/// How the clock and date are shown.
+#[derive(Clone, Debug)]
+pub struct ClockFormat {
+    time: String,
+    short_time: String,
+    date: String,
+    zone: Zone,
+    locale: Locale,
+}
+
+impl ClockFormat {
+    /// `time` is used for the main clock, `short_time` for times of other events (like
+    /// sunrise), and `date` underneath the clock. All are strftime formats, with `%o`
+    /// standing in for the ordinal suffix of the day ("st", "nd", ...). Day and month
+    /// names are translated for the locale.
+    pub fn new(
+        time: &str,
+        short_time: &str,
+        date: &str,
+        zone: Zone,
+        locale: Locale,
+    ) -> Result<Self, String> {
+        for format in &[time, short_time, date] {
+            validate(format)?;
+        }
+
+        Ok(ClockFormat {
+            time: time.to_string(),
+            short_time: short_time.to_string(),
+            date: date.to_string(),
+            zone,
+            locale,
+        })
+    }
+
+    /// Formats the main clock.
+    pub fn time(&self, now: DateTime<Utc>) -> String {
+        self.format(now, &self.time)
+    }
+
+    /// Formats the time of something other than now, e.g. sunrise.
+    pub fn short_time(&self, time: DateTime<Utc>) -> String {
+        self.format(time, &self.short_time)
+    }
+
+    /// Formats the date shown underneath the clock.
+    pub fn date(&self, now: DateTime<Utc>) -> String {
+        self.format(now, &self.date)
+    }
+
+    /// Formats a time in the configured zone with an arbitrary format.
+    pub fn format(&self, time: DateTime<Utc>, format: &str) -> String {
+        let strings = self.locale.strings();
+
+        match self.zone {
+            Zone::Local => format_in(&time.with_timezone(&Local), format, strings),
+            Zone::Named(tz) => format_in(&time.with_timezone(&tz), format, strings),
+        }
+    }
+
+    /// The language names are translated into.
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    /// Returns the date and time as the clock shows it.
+    pub fn naive_local(&self, time: DateTime<Utc>) -> NaiveDateTime {
+        match self.zone {
+            Zone::Local => time.with_timezone(&Local).naive_local(),
+            Zone::Named(tz) => time.with_timezone(&tz).naive_local(),
+        }
+    }
+
+    /// Checks the sleep schedule against the clock shown on screen.
+    pub fn is_night(&self, schedule: &Schedule, now: DateTime<Utc>) -> bool {
+        match self.zone {
+            Zone::Local => schedule.is_night(&now.with_timezone(&Local)),
+            Zone::Named(tz) => schedule.is_night(&now.with_timezone(&tz)),
+        }
+    }
+
+    /// Returns true if the sleep schedule turns the display off.
+    pub fn is_off(&self, schedule: &Schedule, now: DateTime<Utc>) -> bool {
+        match self.zone {
+            Zone::Local => schedule.is_off(&now.with_timezone(&Local)),
+            Zone::Named(tz) => schedule.is_off(&now.with_timezone(&tz)),
+        }
+    }
+
+    /// Returns how long until the sleep schedule next changes, if within `limit`.
+    pub fn until_change(
+        &self,
+        schedule: &Schedule,
+        now: DateTime<Utc>,
+        limit: chrono::Duration,
+    ) -> Option<chrono::Duration> {
+        match self.zone {
+            Zone::Local => schedule.until_change(&now.with_timezone(&Local), limit),
+            Zone::Named(tz) => schedule.until_change(&now.with_timezone(&tz), limit),
+        }
+    }
+
+    /// Works out when a timed override expires, reading `until` on this clock.
+    pub fn expiry(
+        &self,
+        duration: Option<&str>,
+        until: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>, String> {
+        match self.zone {
+            Zone::Local => parse_expiry(duration, until, &now.with_timezone(&Local)),
+            Zone::Named(tz) => parse_expiry(duration, until, &now.with_timezone(&tz)),
+        }
+    }
+}
+
+/// Remembers when the sleep schedule next changes. Finding that out takes many
+/// checks of the schedule, so it is only redone once a minute rather than every frame.
+pub struct NextChange {
+    limit: chrono::Duration,
+    /// Minute the change was last looked for in, and when it happens.
+    cached: Option<(i64, Option<DateTime<Utc>>)>,
+}
+
+impl NextChange {
+    /// `limit` is the furthest ahead anything will ask about.
+    pub fn new(limit: chrono::Duration) -> Self {
+        NextChange {
+            // Changes just beyond the limit get closer during the minute
+            limit: limit + chrono::Duration::minutes(1),
+            cached: None,
+        }
+    }
+
+    /// Returns how long until the sleep schedule next changes, if within `limit`.
+    pub fn until(
+        &mut self,
+        clock: &ClockFormat,
+        schedule: &Schedule,
+        now: DateTime<Utc>,
+        limit: chrono::Duration,
+    ) -> Option<chrono::Duration> {
+        if limit <= chrono::Duration::zero() {
+            return None;
+        }
+
+        let minute = now.timestamp().div_euclid(60);
+        let stale = match self.cached {
+            Some((checked, next)) => checked != minute || next.is_some_and(|x| x <= now),
+            None => true,
+        };
+        if stale {
+            let next = clock
+                .until_change(schedule, now, self.limit)
+                .map(|x| now + x);
+            self.cached = Some((minute, next));
+        }
+
+        self.cached
+            .and_then(|(_, next)| next)
+            .map(|next| next - now)
+            .filter(|x| *x < limit)
+    }
+}
+
+fn format_in<Tz: TimeZone>(time: &DateTime<Tz>, format: &str, strings: &Strings) -> String
+where
+    Tz::Offset: Display,
+{
+    let format = translate(format, time, strings);
+    time.format(&format).to_string()
+}
+
+/// Replaces day and month names (`%A`, `%a`, `%B`, `%b`, `%h`) and the ordinal suffix
+/// (`%o`) with translations, leaving everything else for chrono.
+fn translate<T: Datelike>(format: &str, date: &T, strings: &Strings) -> String {
+    let weekday = date.weekday().num_days_from_monday() as usize;
+    let month = date.month0() as usize;
+
+    let mut result = String::with_capacity(format.len());
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+
+        let name = match chars.next() {
+            Some('A') => strings.days[weekday],
+            Some('a') => strings.short_days[weekday],
+            Some('B') => strings.months[month],
+            Some('b') | Some('h') => strings.short_months[month],
+            Some('o') => (strings.ordinal)(date.day()),
+            Some(next) => {
+                result.push('%');
+                result.push(next);
+                continue;
+            }
+            None => {
+                result.push('%');
+                continue;
+            }
+        };
+
+        // Names could contain a literal %
+        result += &name.replace('%', "%%");
+    }
+
+    result
+}
+
+fn validate(format: &str) -> Result<(), String> {
+    let expanded = translate(format, &NaiveDate::MIN, Locale::English.strings());
+
+    if StrftimeItems::new(&expanded).any(|x| x == Item::Error) {
+        Err(format!("Invalid time format: {:?}", format))
     } else {
-        time.date_naive().and_time(end_time)
-    };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::schedule::{Days, SleepWindow, TimeOfDay};
+
+    use chrono_tz::America::New_York;
+
+    fn clock(time: &str, date: &str, zone: Zone) -> ClockFormat {
+        ClockFormat::new(time, "%-I:%M %P", date, zone, Locale::English).unwrap()
+    }
+
+    fn utc(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn formats_in_zone() {
+        let sydney = clock(
+            "%-I:%M:%S %P",
+            "%A, %-d%o of %B",
+            Zone::Named(chrono_tz::Australia::Sydney),
+        );
+        let now = utc("2024-09-14T11:05:09Z");
+
+        assert_eq!(sydney.time(now), "9:05:09 pm");
+        assert_eq!(sydney.date(now), "Saturday, 14th of September");
+
+        let new_york = clock("%H:%M", "%Y-%m-%d", Zone::Named(New_York));
+        assert_eq!(new_york.time(now), "07:05");
+        assert_eq!(new_york.short_time(now), "7:05 am");
+        assert_eq!(new_york.date(now), "2024-09-14");
+    }
+
+    #[test]
+    fn caches_next_change() {
+        let window = SleepWindow {
+            days: Days::default(),
+            sleep: TimeOfDay::from_hm(22, 0).unwrap(),
+            wake: TimeOfDay::from_hm(7, 30).unwrap(),
+        };
+        let mut schedule = Schedule::new(vec![window], None);
+        let clock = clock("%H:%M", "%Y-%m-%d", Zone::Named(Tz::UTC));
+        let mut next_change = NextChange::new(chrono::Duration::hours(1));
+
+        let now = utc("2024-09-02T21:15:00Z");
+        let limit = chrono::Duration::hours(1);
+        assert_eq!(
+            next_change.until(&clock, &schedule, now, limit),
+            Some(chrono::Duration::minutes(45))
+        );
+        assert_eq!(
+            next_change.until(&clock, &schedule, now, chrono::Duration::minutes(30)),
+            None
+        );
+
+        // Only looked for again once the minute changes
+        schedule = Schedule::new(Vec::new(), None);
+        let later = now + chrono::Duration::seconds(30);
+        assert_eq!(
+            next_change.until(&clock, &schedule, later, limit),
+            Some(chrono::Duration::seconds(44 * 60 + 30))
+        );
+        let later = now + chrono::Duration::minutes(1);
+        assert_eq!(next_change.until(&clock, &schedule, later, limit), None);
+    }
+
+    #[test]
+    fn reads_expiry_in_zone() {
+        let new_york = clock("%H:%M", "%Y-%m-%d", Zone::Named(New_York));
+        let now = utc("2024-09-14T11:05:09Z");
+
+        assert_eq!(
+            new_york.expiry(None, Some("08:00"), now),
+            Ok(Some(utc("2024-09-14T12:00:00Z")))
+        );
+        assert_eq!(
+            new_york.expiry(None, Some("07:00"), now),
+            Ok(Some(utc("2024-09-15T11:00:00Z")))
+        );
+    }
+
+    #[test]
+    fn adds_ordinals() {
+        let clock = clock("%H:%M", "%-d%o", Zone::Named(Tz::UTC));
+
+        let days: Vec<String> = [1, 2, 3, 4, 11, 12, 13, 21, 22, 23, 31]
+            .iter()
+            .map(|day| clock.date(Utc.with_ymd_and_hms(2024, 1, *day, 12, 0, 0).unwrap()))
+            .collect();
+
+        assert_eq!(
+            days,
+            vec![
+                "1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "22nd", "23rd", "31st"
+            ]
+        );
+
+        let date = NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+        assert_eq!(
+            translate("%%o %-d%o%", &date, Locale::English.strings()),
+            "%%o %-dst%"
+        );
+    }
+
+    #[test]
+    fn translates_names() {
+        let now = utc("2024-09-01T11:05:09Z");
+        let format = |locale, date: &str| {
+            ClockFormat::new("%H:%M", "%H:%M", date, Zone::Named(Tz::UTC), locale)
+                .unwrap()
+                .date(now)
+        };
+
+        assert_eq!(
+            format(Locale::German, Locale::German.strings().date_format),
+            "Sonntag, 1. September"
+        );
+        assert_eq!(
+            format(Locale::French, Locale::French.strings().date_format),
+            "dimanche 1er septembre"
+        );
+        assert_eq!(
+            format(Locale::Spanish, Locale::Spanish.strings().date_format),
+            "domingo, 1 de septiembre"
+        );
+        assert_eq!(format(Locale::German, "%a %-d %b %Y"), "So 1 Sep 2024");
+    }
+
+    #[test]
+    fn rejects_invalid_formats() {
+        let new = |time, date| ClockFormat::new(time, "%H:%M", date, Zone::Local, Locale::English);
 
-    cur_date > start_date && cur_date < end_date
+        assert!(new("%H:%M", "%-d%o of %B").is_ok());
+        assert!(new("%H:%Q", "%d").is_err());
+        assert!(new("%H:%M", "%").is_err());
+    }
 }
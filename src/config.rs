@@ -3,7 +3,33 @@ use std::io::Read;
 
 use toml;
 
-use leaffront_weather::WeatherProviderKind;
+use leaffront_core::brightness::{Backlight, BrightnessControl, BACKLIGHT_CLASS};
+#[cfg(target_os = "linux")]
+use leaffront_core::ddc::{Ddc, I2C_DEVICES};
+use leaffront_core::light::{LightSensor, IIO_DEVICES};
+#[cfg(target_os = "linux")]
+use leaffront_core::presence::{PresenceSensor, PresenceSource};
+use leaffront_core::render::color::Color;
+
+use leaffront_weather::manager::RetryPolicy;
+
+use egui::{Color32, FontDefinitions, Vec2};
+
+use crate::ambient::AmbientSettings;
+use crate::clock::{ClockFormat, Zone};
+use crate::dimmer::Curve;
+use crate::filter::{FilterSettings, NEUTRAL_TEMPERATURE};
+use crate::fonts::{load_fonts, FontFile};
+use crate::locale::Locale;
+use crate::power::{PowerControl, PowerMethod};
+use crate::schedule::{Days, Schedule, ScheduleException, SleepWindow, TimeOfDay};
+use crate::subtitle::{Subtitle, Subtitles};
+use crate::sun::Coordinates;
+use crate::theme::{parse_colour, Anchor, Panel, PanelStyle, Panels};
+use leaffront_weather::{WeatherProviderConfig, WeatherProviderKind};
+
+use std::path::Path;
+use std::time::Duration;
 
 #[derive(Deserialize, Debug)]
 pub struct LeaffrontConfig {
@@ -14,13 +40,647 @@ pub struct LeaffrontConfig {
     pub night: Night,
     pub weather: Weather,
     pub fullscreen: bool,
+    #[serde(default)]
+    pub clock: Clock,
+    #[serde(default)]
+    pub brightness: Brightness,
+    /// Dims the display to suit the room, if a light sensor is available.
+    #[serde(default)]
+    pub ambient: Option<Ambient>,
+    #[serde(default)]
+    pub power: Power,
+    /// Wakes the display when someone is around, if a presence sensor is available.
+    #[serde(default)]
+    pub presence: Option<Presence>,
+    /// Language for dates, on-screen text and weather descriptions, e.g. "de-DE".
+    #[serde(default)]
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub theme: Theme,
+    #[serde(default)]
+    pub layout: Layout,
+    #[serde(default)]
+    pub fonts: Fonts,
+    #[serde(default)]
+    pub filter: Filter,
+}
+
+impl LeaffrontConfig {
+    /// Returns the language to show text in, falling back to English if there are
+    /// no translations for the configured one.
+    pub fn get_locale(&self) -> Locale {
+        match &self.locale {
+            Some(tag) => Locale::from_tag(tag).unwrap_or_else(|| {
+                warn!("No translations for locale {:?}, using English", tag);
+                Locale::English
+            }),
+            None => Locale::English,
+        }
+    }
+
+    /// Places and styles the Day, Night and notification panels, falling back to
+    /// `[theme]` for anything not set in `[layout]`.
+    pub fn get_panels(&self) -> Panels {
+        let theme = &self.theme.panel;
+
+        // The Night clock is drawn straight over the background, unless asked otherwise
+        let night_theme = PanelTheme {
+            background: Some("#00000000".to_string()),
+            ..theme.clone()
+        };
+
+        Panels {
+            day: self
+                .layout
+                .day
+                .get_panel(theme, Anchor::BottomLeft, (10.0, -10.0), 0.0),
+            night: self
+                .layout
+                .night
+                .get_panel(&night_theme, Anchor::Center, (0.0, 0.0), 0.0),
+            night_moves: self.layout.night.anchor.is_none(),
+            notifications: self.layout.notifications.get_panel(
+                theme,
+                Anchor::TopRight,
+                (-10.0, 50.0),
+                120.0,
+            ),
+        }
+    }
+
+    /// Returns all configured weather locations, asking providers for descriptions
+    /// in the configured language.
+    pub fn get_weather_locations(&self) -> Vec<WeatherLocation> {
+        let mut locations = self.weather.get_locations();
+
+        if let Some(tag) = &self.locale {
+            for location in &mut locations {
+                location.set_language(tag);
+            }
+        }
+
+        locations
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Sleep {
-    pub sleep_hour: u32,
-    pub wakeup_hour: u32,
-    pub http_endpoint: Option<String>
+    /// Hour to sleep at every day. Ignored if `windows` is set.
+    #[serde(default)]
+    pub sleep_hour: Option<u32>,
+    #[serde(default)]
+    pub wakeup_hour: Option<u32>,
+    /// Periods to sleep in, which may differ by day of the week.
+    #[serde(default)]
+    pub windows: Vec<SleepWindow>,
+    /// Location to compute sunrise and sunset for, in decimal degrees. Needed for
+    /// windows relative to the sun.
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    /// Dates which don't follow the regular windows, e.g. holidays.
+    #[serde(default)]
+    pub exceptions: Vec<ScheduleException>,
+    pub http_endpoint: Option<String>,
+    /// File used to keep REST API overrides across restarts
+    #[serde(default)]
+    pub override_file: Option<String>,
+}
+
+impl Sleep {
+    /// Builds the sleep schedule, falling back to `sleep_hour`/`wakeup_hour` if no
+    /// windows were configured.
+    pub fn get_schedule(&self) -> Schedule {
+        let windows = if !self.windows.is_empty() {
+            self.windows.clone()
+        } else {
+            match (self.sleep_hour, self.wakeup_hour) {
+                (Some(sleep), Some(wake)) => {
+                    let hour = |name: &str, hour: u32| {
+                        TimeOfDay::from_hm(hour, 0).unwrap_or_else(|| {
+                            panic!("{} must be between 0 and 23, got {}", name, hour)
+                        })
+                    };
+
+                    vec![SleepWindow {
+                        days: Days::default(),
+                        sleep: hour("sleep_hour", sleep),
+                        wake: hour("wakeup_hour", wake),
+                    }]
+                }
+                _ => {
+                    warn!("No sleep schedule configured, staying awake");
+                    Vec::new()
+                }
+            }
+        };
+
+        let location = match (self.latitude, self.longitude) {
+            (Some(latitude), Some(longitude)) => Some(Coordinates {
+                latitude,
+                longitude,
+            }),
+            _ => None,
+        };
+
+        let mut schedule = Schedule::new(windows, location);
+        for exception in &self.exceptions {
+            schedule.add_exception(exception.clone());
+        }
+
+        if schedule.is_sun_relative() && location.is_none() {
+            panic!("Sleep windows relative to the sun need a latitude and longitude");
+        }
+
+        schedule
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Clock {
+    /// Show times as 24-hour rather than 12-hour.
+    #[serde(default)]
+    pub twenty_four_hour: bool,
+    /// Show seconds on the main clock. Defaults to true.
+    #[serde(default)]
+    pub seconds: Option<bool>,
+    /// strftime format for the main clock, overriding the two options above.
+    #[serde(default)]
+    pub time_format: Option<String>,
+    /// strftime format for the date. `%o` is the ordinal suffix of the day.
+    #[serde(default)]
+    pub date_format: Option<String>,
+    /// IANA time zone, e.g. "Australia/Sydney". Defaults to the system's.
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+impl Clock {
+    /// Builds the clock format, filling in defaults for anything not configured.
+    pub fn get_format(&self, locale: Locale) -> ClockFormat {
+        let short_time = if self.twenty_four_hour {
+            "%H:%M"
+        } else {
+            "%-I:%M %P"
+        };
+
+        let time = match &self.time_format {
+            Some(format) => format.clone(),
+            None => match (self.twenty_four_hour, self.seconds.unwrap_or(true)) {
+                (true, true) => "%H:%M:%S".to_string(),
+                (true, false) => "%H:%M".to_string(),
+                (false, true) => "%-I:%M:%S %P".to_string(),
+                (false, false) => "%-I:%M %P".to_string(),
+            },
+        };
+
+        let date = self
+            .date_format
+            .clone()
+            .unwrap_or_else(|| locale.strings().date_format.to_string());
+
+        let zone = match &self.timezone {
+            Some(name) => Zone::Named(
+                name.parse()
+                    .unwrap_or_else(|e| panic!("Unknown time zone {:?}: {}", name, e)),
+            ),
+            None => Zone::Local,
+        };
+
+        ClockFormat::new(&time, short_time, &date, zone, locale).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+/// What sets the display's brightness.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Controller {
+    /// A backlight under /sys/class/backlight, e.g. a built-in panel.
+    #[default]
+    Backlight,
+    /// An external monitor, through DDC/CI over I2C.
+    Ddc,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Brightness {
+    #[serde(default)]
+    pub controller: Controller,
+    /// Device to control. For backlights, a name in /sys/class/backlight or a path
+    /// to one. For DDC/CI, an I2C bus like /dev/i2c-2. Searched for if unset.
+    #[serde(default)]
+    pub device: Option<String>,
+    /// Seconds to fade over when switching between Day and Night. Defaults to 2.
+    #[serde(default)]
+    pub fade_secs: Option<f32>,
+    #[serde(default)]
+    pub curve: Curve,
+    /// Minutes before waking to start brightening the Night display.
+    #[serde(default)]
+    pub dawn_mins: Option<u32>,
+    /// Minutes before sleeping to start dimming the Day display.
+    #[serde(default)]
+    pub dusk_mins: Option<u32>,
+}
+
+impl Brightness {
+    /// Opens the configured brightness control, or the most suitable one.
+    pub fn open_control(&self) -> Option<Box<dyn BrightnessControl>> {
+        fn boxed<T: BrightnessControl + 'static>(control: T) -> Box<dyn BrightnessControl> {
+            Box::new(control)
+        }
+
+        let control = match (self.controller, &self.device) {
+            (Controller::Backlight, Some(device)) if device.contains('/') => {
+                Backlight::open(Path::new(device)).map(boxed)
+            }
+            (Controller::Backlight, device) => {
+                Backlight::find(Path::new(BACKLIGHT_CLASS), device.as_deref()).map(boxed)
+            }
+            #[cfg(target_os = "linux")]
+            (Controller::Ddc, Some(device)) => {
+                Ddc::open(Path::new(device)).and_then(Ddc::spawn).map(boxed)
+            }
+            #[cfg(target_os = "linux")]
+            (Controller::Ddc, None) => Ddc::find(Path::new(I2C_DEVICES))
+                .and_then(Ddc::spawn)
+                .map(boxed),
+            #[cfg(not(target_os = "linux"))]
+            (Controller::Ddc, _) => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "DDC/CI is only supported on Linux",
+            )),
+        };
+
+        match control {
+            Ok(control) => {
+                info!("Using brightness control {}", control.name());
+                Some(control)
+            }
+            Err(e) => {
+                warn!("No backlight available, brightness won't change: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn get_fade(&self) -> Duration {
+        Duration::from_secs_f32(self.fade_secs.unwrap_or(2.0).max(0.0))
+    }
+
+    pub fn get_dawn(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.dawn_mins.unwrap_or(0) as i64)
+    }
+
+    pub fn get_dusk(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.dusk_mins.unwrap_or(0) as i64)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Ambient {
+    /// IIO device directory or illuminance channel file. Searched for if unset.
+    #[serde(default)]
+    pub sensor: Option<String>,
+    /// [lux, brightness] points to interpolate between.
+    #[serde(default)]
+    pub curve: Vec<(f32, f32)>,
+    /// Seconds for readings to settle after the light changes. Defaults to 5.
+    #[serde(default)]
+    pub smoothing_secs: Option<f32>,
+    /// How far brightness has to move before it's changed. Defaults to 3.
+    #[serde(default)]
+    pub hysteresis: Option<f32>,
+    #[serde(default)]
+    pub min_brightness: Option<u8>,
+    #[serde(default)]
+    pub max_brightness: Option<u8>,
+}
+
+impl Ambient {
+    pub fn get_settings(&self) -> AmbientSettings {
+        let curve = if self.curve.is_empty() {
+            vec![
+                (0.0, 10.0),
+                (10.0, 25.0),
+                (50.0, 50.0),
+                (200.0, 80.0),
+                (500.0, 100.0),
+            ]
+        } else {
+            self.curve.clone()
+        };
+
+        AmbientSettings {
+            curve,
+            smoothing: Duration::from_secs_f32(self.smoothing_secs.unwrap_or(5.0).max(0.0)),
+            hysteresis: self.hysteresis.unwrap_or(3.0),
+            min_brightness: self.min_brightness.unwrap_or(0) as f32,
+            max_brightness: self.max_brightness.unwrap_or(100) as f32,
+        }
+    }
+
+    /// Opens the configured light sensor, or the first one that can be found.
+    pub fn open_sensor(&self) -> Option<LightSensor> {
+        let sensor = match &self.sensor {
+            Some(path) => LightSensor::open(Path::new(path)).map(Some),
+            None => LightSensor::find(Path::new(IIO_DEVICES)),
+        };
+
+        match sensor {
+            Ok(Some(sensor)) => {
+                info!("Using light sensor {:?}", sensor.path());
+                Some(sensor)
+            }
+            Ok(None) => {
+                warn!("No light sensor found, ignoring ambient light");
+                None
+            }
+            Err(e) => {
+                warn!("Failed to open light sensor: {:?}", e);
+                None
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Power {
+    #[serde(default)]
+    pub method: PowerMethod,
+    /// Shell commands for the `command` method.
+    #[serde(default)]
+    pub on_command: Option<String>,
+    #[serde(default)]
+    pub off_command: Option<String>,
+    /// Minutes without a touch before the Night display turns off. Never, if unset.
+    #[serde(default)]
+    pub off_after_mins: Option<u32>,
+}
+
+impl Power {
+    pub fn get_control(&self) -> PowerControl {
+        PowerControl {
+            method: self.method,
+            on_command: self.on_command.clone(),
+            off_command: self.off_command.clone(),
+        }
+    }
+
+    pub fn get_off_after(&self) -> Option<Duration> {
+        self.off_after_mins
+            .map(|x| Duration::from_secs(x as u64 * 60))
+    }
+}
+
+/// Look of every panel, unless overridden in `[layout]`.
+#[derive(Deserialize, Debug, Default)]
+pub struct Theme {
+    #[serde(flatten)]
+    pub panel: PanelTheme,
+    /// Size of smaller text, e.g. on the temperature history page. Defaults to 14.
+    #[serde(default)]
+    pub body_size: Option<f32>,
+}
+
+impl Theme {
+    pub fn get_style(&self) -> PanelStyle {
+        self.panel.get_style()
+    }
+
+    pub fn get_body_size(&self) -> f32 {
+        self.body_size.unwrap_or(14.0)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct PanelTheme {
+    /// Size of the clock and most other text. Defaults to 40.
+    #[serde(default)]
+    pub heading_size: Option<f32>,
+    /// Colours are written as "#RRGGBB" or "#RRGGBBAA".
+    #[serde(default)]
+    pub text_colour: Option<String>,
+    #[serde(default)]
+    pub background: Option<String>,
+    /// Multiplies the background's alpha, from 0 to 1.
+    #[serde(default)]
+    pub opacity: Option<f32>,
+    #[serde(default)]
+    pub padding: Option<f32>,
+    #[serde(default)]
+    pub corner_radius: Option<f32>,
+}
+
+impl PanelTheme {
+    /// Fills in anything not set here from another theme.
+    fn or(&self, fallback: &PanelTheme) -> PanelTheme {
+        PanelTheme {
+            heading_size: self.heading_size.or(fallback.heading_size),
+            text_colour: self
+                .text_colour
+                .clone()
+                .or_else(|| fallback.text_colour.clone()),
+            background: self
+                .background
+                .clone()
+                .or_else(|| fallback.background.clone()),
+            opacity: self.opacity.or(fallback.opacity),
+            padding: self.padding.or(fallback.padding),
+            corner_radius: self.corner_radius.or(fallback.corner_radius),
+        }
+    }
+
+    fn get_style(&self) -> PanelStyle {
+        let colour = |colour: &Option<String>, default: &str| {
+            parse_colour(colour.as_deref().unwrap_or(default)).unwrap_or_else(|e| panic!("{}", e))
+        };
+
+        let [r, g, b, _] = colour(&self.text_colour, "#FFFFFF");
+        let text = Color32::from_rgb(r, g, b);
+
+        let [r, g, b, a] = colour(&self.background, "#141414DC");
+        let opacity = self.opacity.unwrap_or(1.0).clamp(0.0, 1.0);
+        let background =
+            Color32::from_rgba_unmultiplied(r, g, b, (a as f32 * opacity).round() as u8);
+
+        PanelStyle {
+            heading_size: self.heading_size.unwrap_or(40.0),
+            text,
+            background,
+            padding: self.padding.unwrap_or(15.0),
+            corner_radius: self.corner_radius.unwrap_or(6.0),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Layout {
+    #[serde(default)]
+    pub day: PanelLayout,
+    #[serde(default)]
+    pub night: PanelLayout,
+    /// Weather warnings and notifications, stacked on top of each other.
+    #[serde(default)]
+    pub notifications: PanelLayout,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct PanelLayout {
+    /// Where the panel goes. Without one, the Night panel moves around the screen.
+    #[serde(default)]
+    pub anchor: Option<Anchor>,
+    /// [x, y] from the anchor, in pixels.
+    #[serde(default)]
+    pub offset: Option<(f32, f32)>,
+    /// Distance between stacked notifications.
+    #[serde(default)]
+    pub spacing: Option<f32>,
+    #[serde(flatten)]
+    pub theme: PanelTheme,
+}
+
+impl PanelLayout {
+    fn get_panel(
+        &self,
+        theme: &PanelTheme,
+        anchor: Anchor,
+        offset: (f32, f32),
+        spacing: f32,
+    ) -> Panel {
+        let (x, y) = self.offset.unwrap_or(offset);
+
+        Panel {
+            anchor: self.anchor.unwrap_or(anchor).align(),
+            offset: Vec2::new(x, y),
+            spacing: self.spacing.unwrap_or(spacing),
+            style: self.theme.or(theme).get_style(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Fonts {
+    #[serde(default)]
+    pub files: Vec<FontFile>,
+    /// Names of the fonts for each kind of text, in order of preference.
+    #[serde(default)]
+    pub heading: Vec<String>,
+    #[serde(default)]
+    pub body: Vec<String>,
+    #[serde(default)]
+    pub monospace: Vec<String>,
+}
+
+impl Fonts {
+    pub fn get_definitions(&self) -> FontDefinitions {
+        load_fonts(&self.files, &self.heading, &self.body, &self.monospace)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+/// Colors everything drawn, independent of brightness.
+#[derive(Deserialize, Debug, Default)]
+pub struct Filter {
+    /// Color temperature of the Day display in kelvin. Defaults to 6500, which is neutral.
+    #[serde(default)]
+    pub day_temperature: Option<f32>,
+    /// Temperature to warm up to before sleeping. Defaults to the Day temperature.
+    #[serde(default)]
+    pub evening_temperature: Option<f32>,
+    /// Minutes before sleeping to warm up over. Defaults to 120.
+    #[serde(default)]
+    pub warm_mins: Option<u32>,
+    /// Color of the Night display as "#RRGGBB", e.g. a deep red. Defaults to the
+    /// evening temperature.
+    #[serde(default)]
+    pub night_tint: Option<String>,
+}
+
+impl Filter {
+    pub fn get_settings(&self) -> FilterSettings {
+        let day_temperature = self.day_temperature.unwrap_or(NEUTRAL_TEMPERATURE);
+
+        let night_tint = self.night_tint.as_ref().map(|tint| {
+            let [r, g, b, _] = parse_colour(tint).unwrap_or_else(|e| panic!("{}", e));
+            Color::new_3byte(r, g, b)
+        });
+
+        FilterSettings {
+            day_temperature,
+            evening_temperature: self.evening_temperature.unwrap_or(day_temperature),
+            warm: chrono::Duration::minutes(self.warm_mins.unwrap_or(120) as i64),
+            night_tint,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Presence {
+    /// GPIO chip and line a PIR sensor is connected to, e.g. "/dev/gpiochip0".
+    #[serde(default)]
+    pub gpio_chip: Option<String>,
+    #[serde(default)]
+    pub gpio_line: Option<u32>,
+    #[serde(default)]
+    pub active_low: bool,
+    /// evdev device reporting a key or switch, used if no GPIO line is set.
+    #[serde(default)]
+    pub device: Option<String>,
+    /// Key or switch code to watch. Any, if unset.
+    #[serde(default)]
+    pub code: Option<u16>,
+    /// Minutes with nobody around before the display goes back to sleep. Defaults to 10.
+    #[serde(default)]
+    pub idle_mins: Option<u32>,
+    /// Turn the display off when nobody is around, even when it would otherwise be Day.
+    #[serde(default)]
+    pub off_when_away: bool,
+}
+
+#[cfg(target_os = "linux")]
+impl Presence {
+    pub fn get_source(&self) -> Result<PresenceSource, String> {
+        match (&self.gpio_chip, self.gpio_line, &self.device) {
+            (Some(chip), Some(line), _) => Ok(PresenceSource::Gpio {
+                chip: chip.into(),
+                line,
+                active_low: self.active_low,
+            }),
+            (None, Some(line), _) => Ok(PresenceSource::Gpio {
+                chip: "/dev/gpiochip0".into(),
+                line,
+                active_low: self.active_low,
+            }),
+            (_, None, Some(device)) => Ok(PresenceSource::Evdev {
+                device: device.into(),
+                code: self.code,
+            }),
+            _ => Err("Either gpio_line or device needs to be set".to_string()),
+        }
+    }
+
+    pub fn open_sensor(&self) -> Option<PresenceSensor> {
+        let sensor = self
+            .get_source()
+            .and_then(|source| PresenceSensor::open(&source).map_err(|e| format!("{:?}", e)));
+
+        match sensor {
+            Ok(sensor) => {
+                info!("Watching for presence");
+                Some(sensor)
+            }
+            Err(e) => {
+                warn!("Failed to open presence sensor: {}", e);
+                None
+            }
+        }
+    }
+
+    pub fn get_idle(&self) -> Duration {
+        Duration::from_secs(self.idle_mins.unwrap_or(10) as u64 * 60)
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -35,13 +695,127 @@ pub struct Day {
     pub background_secs: u64,
     pub subtitle_secs: u64,
     pub brightness: u8,
+    /// What to rotate through under the clock, in order.
+    #[serde(default)]
+    pub subtitles: Vec<Subtitle>,
+}
+
+impl Day {
+    pub fn get_subtitles(&self, weather_locations: usize) -> Subtitles {
+        Subtitles::new(&self.subtitles, self.subtitle_secs, weather_locations)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Weather {
     pub update_freq: u64,
+    /// Provider for a single, unnamed location. Ignored if `locations` is set.
+    #[serde(default)]
+    pub kind: Option<WeatherProviderKind>,
+    #[serde(default)]
+    pub config: Option<toml::Value>,
+    /// Providers to fail over to for the single location, in order.
+    #[serde(default)]
+    pub fallback: Vec<WeatherProviderConfig>,
+    /// Locations to rotate through in the weather subtitle.
+    #[serde(default)]
+    pub locations: Vec<WeatherLocation>,
+    /// Switch to Day mode when a new severe weather warning is issued.
+    #[serde(default)]
+    pub wake_on_warning: bool,
+    /// Seconds to wait after the first failed update. Doubles with each failure.
+    #[serde(default)]
+    pub retry_min_secs: Option<u64>,
+    /// Upper bound on the wait between failed updates.
+    #[serde(default)]
+    pub retry_max_secs: Option<u64>,
+    /// Consecutive failures before failing over to the next provider.
+    #[serde(default)]
+    pub failover_after: Option<u32>,
+    /// File to record temperatures from the first location into. History is kept
+    /// in memory only if unset.
+    #[serde(default)]
+    pub history_file: Option<String>,
+    /// How many hours of temperature history to keep. Defaults to 24.
+    #[serde(default)]
+    pub history_hours: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WeatherLocation {
+    /// Shown alongside the weather, e.g. "Home".
+    #[serde(default)]
+    pub name: Option<String>,
     pub kind: WeatherProviderKind,
+    #[serde(default)]
     pub config: Option<toml::Value>,
+    /// Providers to fail over to if `kind` keeps failing, in order.
+    #[serde(default)]
+    pub fallback: Vec<WeatherProviderConfig>,
+}
+
+impl WeatherLocation {
+    /// Returns the primary provider followed by any fallbacks.
+    pub fn get_providers(&self) -> Vec<WeatherProviderConfig> {
+        let mut providers = vec![WeatherProviderConfig {
+            kind: self.kind,
+            config: self.config.clone(),
+        }];
+        providers.extend(self.fallback.iter().cloned());
+        providers
+    }
+
+    /// Asks every provider for this location to describe the weather in a language.
+    pub fn set_language(&mut self, tag: &str) {
+        let mut primary = WeatherProviderConfig {
+            kind: self.kind,
+            config: self.config.take(),
+        };
+        primary.set_language(tag);
+        self.config = primary.config;
+
+        for fallback in &mut self.fallback {
+            fallback.set_language(tag);
+        }
+    }
+}
+
+impl Weather {
+    /// Builds the retry policy, falling back to defaults for anything not configured.
+    pub fn get_retry_policy(&self) -> RetryPolicy {
+        let mut policy = RetryPolicy::default();
+
+        if let Some(secs) = self.retry_min_secs {
+            policy.initial_delay = Duration::from_secs(secs);
+        }
+        if let Some(secs) = self.retry_max_secs {
+            policy.max_delay = Duration::from_secs(secs);
+        }
+        if let Some(failover_after) = self.failover_after {
+            policy.failover_after = failover_after;
+        }
+
+        policy
+    }
+
+    /// Returns all configured locations, falling back to the top-level `kind`/`config`
+    /// pair if no explicit locations were set.
+    pub fn get_locations(&self) -> Vec<WeatherLocation> {
+        if !self.locations.is_empty() {
+            return self.locations.clone();
+        }
+
+        match self.kind {
+            Some(kind) => vec![WeatherLocation {
+                name: None,
+                kind,
+                config: self.config.clone(),
+                fallback: self.fallback.clone(),
+            }],
+            None => Vec::new(),
+        }
+    }
 }
 
 /// Loads a configuration file.
@@ -0,0 +1,209 @@
+//! Fades the backlight between levels, rather than jumping straight to them.
+
+use std::time::{Duration, Instant};
+
+/// How often to redraw while fading.
+pub static FADE_INTERVAL: Duration = Duration::from_millis(30);
+
+/// How brightness moves between two levels.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Curve {
+    /// Changes at a constant rate.
+    #[default]
+    Linear,
+    /// Starts and finishes slowly.
+    Smooth,
+    /// Changes at a constant rate as seen by the eye, which is more sensitive to
+    /// changes at low levels.
+    Perceptual,
+}
+
+/// Gamma used to approximate perceived brightness.
+static PERCEPTUAL_GAMMA: f32 = 2.2;
+
+impl Curve {
+    /// Returns the level `progress` (0 to 1) of the way between two levels.
+    pub fn interpolate(self, from: f32, to: f32, progress: f32) -> f32 {
+        let progress = progress.clamp(0.0, 1.0);
+
+        match self {
+            Curve::Linear => from + (to - from) * progress,
+            Curve::Smooth => {
+                let progress = progress * progress * (3.0 - 2.0 * progress);
+                from + (to - from) * progress
+            }
+            Curve::Perceptual => {
+                let from = from.max(0.0).powf(1.0 / PERCEPTUAL_GAMMA);
+                let to = to.max(0.0).powf(1.0 / PERCEPTUAL_GAMMA);
+                (from + (to - from) * progress).powf(PERCEPTUAL_GAMMA)
+            }
+        }
+    }
+}
+
+/// Returns the brightness while approaching a scheduled change, moving from `from`
+/// to `to` over the `length` before it. Stays at `from` if the change is further
+/// away than that.
+pub fn approach(
+    from: u8,
+    to: u8,
+    until_change: Option<chrono::Duration>,
+    length: chrono::Duration,
+    curve: Curve,
+) -> f32 {
+    let (from, to) = (from as f32, to as f32);
+
+    match until_change {
+        Some(remaining) if remaining < length && length > chrono::Duration::zero() => {
+            let progress =
+                1.0 - remaining.num_milliseconds() as f32 / length.num_milliseconds() as f32;
+            curve.interpolate(from, to, progress)
+        }
+        _ => from,
+    }
+}
+
+struct Fade {
+    from: f32,
+    to: f32,
+    started: Instant,
+    duration: Duration,
+}
+
+/// Tracks the current brightness level and any fade in progress.
+pub struct Dimmer {
+    level: f32,
+    /// Last level handed out to be applied.
+    applied: Option<u8>,
+    fade: Option<Fade>,
+    curve: Curve,
+}
+
+impl Dimmer {
+    pub fn new(level: f32, curve: Curve) -> Self {
+        Dimmer {
+            level,
+            applied: None,
+            fade: None,
+            curve,
+        }
+    }
+
+    /// Starts fading to a new level from wherever the current one is.
+    pub fn fade_to(&mut self, target: f32, duration: Duration, now: Instant) {
+        if self.fade.is_none() && self.level == target {
+            return;
+        }
+
+        if duration.is_zero() {
+            self.level = target;
+            self.fade = None;
+            return;
+        }
+
+        self.fade = Some(Fade {
+            from: self.level,
+            to: target,
+            started: now,
+            duration,
+        });
+    }
+
+    /// Moves the target of the current fade, or jumps straight to the level if not
+    /// fading. Meant for targets that only drift slowly, like dawn and dusk.
+    pub fn retarget(&mut self, target: f32) {
+        match &mut self.fade {
+            Some(fade) => fade.to = target,
+            None => self.level = target,
+        }
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Advances any fade. Returns the level to set the backlight to, if it has
+    /// changed since the last call.
+    pub fn update(&mut self, now: Instant) -> Option<u8> {
+        if let Some(fade) = &self.fade {
+            let elapsed = now.saturating_duration_since(fade.started);
+
+            if elapsed >= fade.duration {
+                self.level = fade.to;
+                self.fade = None;
+            } else {
+                let progress = elapsed.as_secs_f32() / fade.duration.as_secs_f32();
+                self.level = self.curve.interpolate(fade.from, fade.to, progress);
+            }
+        }
+
+        let level = self.level.round().clamp(0.0, 100.0) as u8;
+
+        if self.applied == Some(level) {
+            None
+        } else {
+            self.applied = Some(level);
+            Some(level)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_curves() {
+        for curve in &[Curve::Linear, Curve::Smooth, Curve::Perceptual] {
+            assert_eq!(curve.interpolate(100.0, 12.0, 0.0).round(), 100.0);
+            assert_eq!(curve.interpolate(100.0, 12.0, 1.0).round(), 12.0);
+            assert_eq!(curve.interpolate(100.0, 12.0, 2.0).round(), 12.0);
+        }
+
+        assert_eq!(Curve::Linear.interpolate(0.0, 100.0, 0.25), 25.0);
+        assert!(Curve::Smooth.interpolate(0.0, 100.0, 0.25) < 25.0);
+        assert_eq!(Curve::Smooth.interpolate(0.0, 100.0, 0.5), 50.0);
+        // Spends longer at the dim end
+        assert!(Curve::Perceptual.interpolate(0.0, 100.0, 0.5) < 25.0);
+    }
+
+    #[test]
+    fn fades_between_levels() {
+        let start = Instant::now();
+        let mut dimmer = Dimmer::new(100.0, Curve::Linear);
+
+        assert_eq!(dimmer.update(start), Some(100));
+        assert_eq!(dimmer.update(start), None);
+
+        dimmer.fade_to(20.0, Duration::from_secs(2), start);
+        assert!(dimmer.is_fading());
+        assert_eq!(dimmer.update(start + Duration::from_millis(500)), Some(80));
+
+        // Changing direction halfway starts from the current level
+        dimmer.fade_to(
+            100.0,
+            Duration::from_secs(1),
+            start + Duration::from_millis(500),
+        );
+        assert_eq!(dimmer.update(start + Duration::from_secs(1)), Some(90));
+        assert_eq!(dimmer.update(start + Duration::from_secs(2)), Some(100));
+        assert!(!dimmer.is_fading());
+
+        dimmer.retarget(99.6);
+        assert_eq!(dimmer.update(start + Duration::from_secs(3)), None);
+        dimmer.fade_to(12.0, Duration::from_secs(0), start);
+        assert_eq!(dimmer.update(start), Some(12));
+    }
+
+    #[test]
+    fn approaches_changes() {
+        let hour = chrono::Duration::hours(1);
+        let approach = |remaining| approach(12, 100, remaining, hour, Curve::Linear);
+
+        assert_eq!(approach(None), 12.0);
+        assert_eq!(approach(Some(chrono::Duration::hours(2))), 12.0);
+        assert_eq!(approach(Some(chrono::Duration::minutes(30))), 56.0);
+        assert_eq!(approach(Some(chrono::Duration::zero())), 100.0);
+    }
+}
@@ -0,0 +1,69 @@
+//! Colors the whole frame, warming it up in the evening and tinting it at night.
+
+use leaffront_core::render::color::Color;
+
+/// Neutral color temperature, which leaves the frame as it is.
+pub static NEUTRAL_TEMPERATURE: f32 = 6500.0;
+
+#[derive(Clone)]
+pub struct FilterSettings {
+    /// Color temperatures in kelvin.
+    pub day_temperature: f32,
+    pub evening_temperature: f32,
+    /// How long before sleeping to warm up over.
+    pub warm: chrono::Duration,
+    /// Color of the Night display. The evening temperature, if unset.
+    pub night_tint: Option<Color>,
+}
+
+impl FilterSettings {
+    /// Returns the color to multiply the frame by, given how long it is until the
+    /// display is scheduled to sleep.
+    pub fn color(&self, night: bool, until_sleep: Option<chrono::Duration>) -> Color {
+        if night {
+            return self
+                .night_tint
+                .clone()
+                .unwrap_or_else(|| Color::from_temperature(self.evening_temperature));
+        }
+
+        let progress = match until_sleep {
+            Some(remaining) if remaining < self.warm && self.warm > chrono::Duration::zero() => {
+                1.0 - remaining.num_milliseconds() as f32 / self.warm.num_milliseconds() as f32
+            }
+            _ => 0.0,
+        };
+
+        Color::from_temperature(
+            self.day_temperature + (self.evening_temperature - self.day_temperature) * progress,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warms_up_before_sleeping() {
+        let mut settings = FilterSettings {
+            day_temperature: NEUTRAL_TEMPERATURE,
+            evening_temperature: 2700.0,
+            warm: chrono::Duration::hours(2),
+            night_tint: None,
+        };
+        let white = Color::new_3byte(255, 255, 255);
+
+        assert!(settings.color(false, None) == white);
+        assert!(settings.color(false, Some(chrono::Duration::hours(3))) == white);
+
+        let halfway = settings.color(false, Some(chrono::Duration::hours(1)));
+        let evening = settings.color(false, Some(chrono::Duration::zero()));
+        assert!(evening == Color::from_temperature(2700.0));
+        assert!(halfway.b < 255 && halfway.b > evening.b);
+
+        assert!(settings.color(true, None) == evening);
+        settings.night_tint = Some(Color::new_3byte(255, 32, 0));
+        assert!(settings.color(true, None) == Color::new_3byte(255, 32, 0));
+    }
+}
@@ -0,0 +1,123 @@
+//! Fonts for on-screen text, loaded into egui at startup.
+
+use egui::{FontData, FontDefinitions, FontFamily};
+
+use std::fs;
+
+/// Name of the font shipped with Leaffront, used unless others are configured.
+pub static DEFAULT_FONT: &str = "Lato";
+static DEFAULT_FONT_DATA: &[u8] = include_bytes!("../res/Lato-Regular.ttf");
+
+/// Family used for headings, e.g. the clock.
+static HEADING_FAMILY: &str = "heading";
+
+/// A font file to load, given a name to refer to it by.
+#[derive(Deserialize, Clone, Debug)]
+pub struct FontFile {
+    pub name: String,
+    /// Path to a TTF or OTF file.
+    pub path: String,
+    /// Face to use from a font collection (.ttc). Defaults to the first.
+    #[serde(default)]
+    pub index: u32,
+}
+
+pub fn heading_family() -> FontFamily {
+    FontFamily::Name(HEADING_FAMILY.into())
+}
+
+/// Loads font files and sets the fonts for each kind of text, by name and in
+/// order of preference. Characters missing from a font fall back to the next
+/// one: headings fall back to the body fonts, and body text to Lato, then egui's
+/// built-in fonts for symbols and emoji.
+pub fn load_fonts(
+    files: &[FontFile],
+    heading: &[String],
+    body: &[String],
+    monospace: &[String],
+) -> Result<FontDefinitions, String> {
+    let mut fonts = FontDefinitions::default();
+
+    fonts.font_data.insert(
+        DEFAULT_FONT.to_string(),
+        FontData::from_static(DEFAULT_FONT_DATA),
+    );
+
+    for file in files {
+        let data = fs::read(&file.path)
+            .map_err(|e| format!("Failed to read font {:?}: {}", file.path, e))?;
+
+        let mut data = FontData::from_owned(data);
+        data.index = file.index;
+        fonts.font_data.insert(file.name.clone(), data);
+    }
+
+    let family = |names: &[String], fallback: &[String]| {
+        let mut family = Vec::new();
+        for name in names.iter().chain(fallback) {
+            if !fonts.font_data.contains_key(name) {
+                return Err(format!("No font file is named {:?}", name));
+            }
+            if !family.contains(name) {
+                family.push(name.clone());
+            }
+        }
+        Ok(family)
+    };
+
+    let builtin = |family: FontFamily| fonts.families.get(&family).cloned().unwrap_or_default();
+    let mut default = vec![DEFAULT_FONT.to_string()];
+    default.extend(builtin(FontFamily::Proportional));
+
+    let body = family(body, &default)?;
+    let heading = family(heading, &body)?;
+    let monospace = family(monospace, &builtin(FontFamily::Monospace))?;
+
+    fonts.families.insert(FontFamily::Proportional, body);
+    fonts.families.insert(heading_family(), heading);
+    fonts.families.insert(FontFamily::Monospace, monospace);
+
+    Ok(fonts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn falls_back_in_order() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("display.ttf");
+        fs::write(&path, DEFAULT_FONT_DATA).unwrap();
+
+        let files = vec![FontFile {
+            name: "Display".to_string(),
+            path: path.display().to_string(),
+            index: 0,
+        }];
+        let fonts = load_fonts(&files, &names(&["Display"]), &[], &[]).unwrap();
+
+        let body = &fonts.families[&FontFamily::Proportional];
+        assert_eq!(body[0], DEFAULT_FONT);
+        assert!(body.len() > 1);
+
+        let heading = &fonts.families[&heading_family()];
+        assert_eq!(heading[0], "Display");
+        assert_eq!(heading[1..], body[..]);
+
+        assert!(load_fonts(&[], &[], &names(&["Missing"]), &[]).is_err());
+
+        let missing = vec![FontFile {
+            name: "Missing".to_string(),
+            path: "/nonexistent/font.ttf".to_string(),
+            index: 0,
+        }];
+        assert!(load_fonts(&missing, &[], &[], &[]).is_err());
+    }
+}
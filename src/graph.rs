@@ -0,0 +1,159 @@
+//! Temperature graphs, drawn directly with egui's painter.
+
+use crate::clock::ClockFormat;
+use crate::history::{Sample, Trend};
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use egui::{Align2, Color32, FontId, Pos2, Rect, Response, Sense, Shape, Stroke, Ui, Vec2};
+
+/// Maps samples into screen space, spanning the time range horizontally and the
+/// temperature range vertically.
+fn plot_points(samples: &[Sample], rect: Rect, start: i64, end: i64) -> Vec<Pos2> {
+    let (min, max) = temperature_range(samples);
+    let span = (end - start).max(1) as f32;
+
+    samples
+        .iter()
+        .map(|sample| {
+            let x = (sample.timestamp - start) as f32 / span;
+            let y = ((sample.temperature - min) / (max - min)) as f32;
+            Pos2::new(
+                rect.left() + x * rect.width(),
+                rect.bottom() - y * rect.height(),
+            )
+        })
+        .collect()
+}
+
+/// Returns the lowest and highest temperatures, padded so a flat line sits centered.
+fn temperature_range(samples: &[Sample]) -> (f64, f64) {
+    let min = samples
+        .iter()
+        .map(|x| x.temperature)
+        .fold(f64::INFINITY, f64::min);
+    let max = samples
+        .iter()
+        .map(|x| x.temperature)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    if max - min < 1.0 {
+        let middle = (min + max) / 2.0;
+        (middle - 0.5, middle + 0.5)
+    } else {
+        (min, max)
+    }
+}
+
+/// Draws a small line graph of the given samples. Responds to clicks.
+pub fn sparkline(ui: &mut Ui, samples: &[Sample], size: Vec2) -> Response {
+    let (response, painter) = ui.allocate_painter(size, Sense::click());
+
+    if let (Some(first), Some(last)) = (samples.first(), samples.last()) {
+        let points = plot_points(samples, response.rect, first.timestamp, last.timestamp);
+        painter.add(Shape::line(points, Stroke::new(2.0, Color32::WHITE)));
+    }
+
+    response
+}
+
+/// Draws an arrow pointing in the direction temperatures are heading.
+pub fn trend_arrow(ui: &mut Ui, trend: Trend, size: f32) -> Response {
+    let (response, painter) = ui.allocate_painter(Vec2::splat(size), Sense::click());
+
+    let rect = response.rect.shrink(size / 6.0);
+    let (origin, direction) = match trend {
+        Trend::Rising => (rect.center_bottom(), Vec2::new(0.0, -rect.height())),
+        Trend::Steady => (rect.left_center(), Vec2::new(rect.width(), 0.0)),
+        Trend::Falling => (rect.center_top(), Vec2::new(0.0, rect.height())),
+    };
+    painter.arrow(origin, direction, Stroke::new(3.0, Color32::WHITE));
+
+    response
+}
+
+/// Draws a full-size graph of temperatures between `start` and `end`, with the
+/// temperature range and time axis labelled. Responds to clicks.
+pub fn history_graph(
+    ui: &mut Ui,
+    samples: &[Sample],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    size: Vec2,
+    clock: &ClockFormat,
+) -> Response {
+    let (response, painter) = ui.allocate_painter(size, Sense::click());
+
+    let font = FontId::proportional(18.0);
+    let label_color = Color32::from_gray(200);
+    let grid = Stroke::new(1.0, Color32::from_gray(90));
+
+    // Leave room for the axis labels
+    let plot = Rect::from_min_max(
+        response.rect.min + Vec2::new(70.0, 10.0),
+        response.rect.max - Vec2::new(10.0, 30.0),
+    );
+
+    if samples.is_empty() {
+        painter.text(
+            plot.center(),
+            Align2::CENTER_CENTER,
+            clock.locale().strings().no_history,
+            font,
+            label_color,
+        );
+        return response;
+    }
+
+    let (min, max) = temperature_range(samples);
+
+    painter.line_segment([plot.left_top(), plot.right_top()], grid);
+    painter.line_segment([plot.left_bottom(), plot.right_bottom()], grid);
+
+    painter.text(
+        plot.left_top() - Vec2::new(10.0, 0.0),
+        Align2::RIGHT_CENTER,
+        format!("{:.0}°", max),
+        font.clone(),
+        label_color,
+    );
+    painter.text(
+        plot.left_bottom() - Vec2::new(10.0, 0.0),
+        Align2::RIGHT_CENTER,
+        format!("{:.0}°", min),
+        font.clone(),
+        label_color,
+    );
+
+    let start = start.timestamp();
+    let end = end.timestamp();
+
+    // Label the start, middle and end of the time axis
+    for (fraction, align) in &[
+        (0.0, Align2::LEFT_TOP),
+        (0.5, Align2::CENTER_TOP),
+        (1.0, Align2::RIGHT_TOP),
+    ] {
+        let timestamp = start + ((end - start) as f64 * fraction) as i64;
+        let label = match Utc.timestamp_opt(timestamp, 0).single() {
+            Some(time) => clock.short_time(time),
+            None => continue,
+        };
+
+        painter.text(
+            Pos2::new(
+                plot.left() + plot.width() * *fraction as f32,
+                plot.bottom() + 8.0,
+            ),
+            *align,
+            label,
+            font.clone(),
+            label_color,
+        );
+    }
+
+    let points = plot_points(samples, plot, start, end);
+    painter.add(Shape::line(points, Stroke::new(3.0, Color32::WHITE)));
+
+    response
+}
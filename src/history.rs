@@ -0,0 +1,287 @@
+//! Records temperature observations into a bounded, on-disk time series.
+
+use chrono::{DateTime, Duration, Utc};
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// A single temperature observation.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    /// Unix timestamp, in seconds.
+    pub timestamp: i64,
+    pub temperature: f64,
+}
+
+/// Which way the temperature has been heading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trend {
+    Rising,
+    Steady,
+    Falling,
+}
+
+/// Minimum change (in degrees) before temperatures are considered to be moving.
+static TREND_THRESHOLD: f64 = 1.0;
+
+/// Observations older than `max_age` are discarded. If a path is set, samples are
+/// appended to it as `timestamp,temperature` lines, and the file is compacted whenever
+/// old samples are dropped.
+pub struct TemperatureHistory {
+    path: Option<PathBuf>,
+    max_age: Duration,
+    samples: VecDeque<Sample>,
+}
+
+impl TemperatureHistory {
+    /// Creates a history, loading any previous samples from the given file.
+    pub fn load(path: Option<PathBuf>, max_age: Duration) -> Self {
+        let mut history = TemperatureHistory {
+            path,
+            max_age,
+            samples: VecDeque::new(),
+        };
+
+        if let Some(path) = &history.path {
+            match File::open(path) {
+                Ok(file) => {
+                    for line in BufReader::new(file).lines().map_while(Result::ok) {
+                        match parse_line(&line) {
+                            Some(sample) => history.samples.push_back(sample),
+                            None => warn!("Ignoring malformed history line: {:?}", line),
+                        }
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => warn!("Failed to read temperature history {:?}: {:?}", path, e),
+            }
+        }
+
+        history
+            .samples
+            .make_contiguous()
+            .sort_by_key(|sample| sample.timestamp);
+        history.prune(Utc::now());
+
+        if let Err(e) = history.save() {
+            warn!("Failed to compact temperature history: {:?}", e);
+        }
+
+        history
+    }
+
+    /// Records a new observation, dropping anything which has aged out.
+    pub fn record(&mut self, time: DateTime<Utc>, temperature: f64) {
+        let sample = Sample {
+            timestamp: time.timestamp(),
+            temperature,
+        };
+        self.samples.push_back(sample);
+
+        let result = if self.prune(time) {
+            self.save()
+        } else {
+            self.append(&sample)
+        };
+
+        if let Err(e) = result {
+            warn!("Failed to persist temperature history: {:?}", e);
+        }
+    }
+
+    /// Returns every retained sample, oldest first.
+    pub fn all(&self) -> Vec<Sample> {
+        self.samples.iter().cloned().collect()
+    }
+
+    /// Returns all samples taken at or after the given time, oldest first.
+    pub fn since(&self, time: DateTime<Utc>) -> Vec<Sample> {
+        let timestamp = time.timestamp();
+
+        self.samples
+            .iter()
+            .filter(|sample| sample.timestamp >= timestamp)
+            .cloned()
+            .collect()
+    }
+
+    /// Compares the latest sample against the oldest one within `window` of it.
+    pub fn trend(&self, window: Duration) -> Option<Trend> {
+        let latest = self.samples.back()?;
+        let start = latest.timestamp - window.num_seconds();
+
+        let reference = self
+            .samples
+            .iter()
+            .find(|sample| sample.timestamp >= start)?;
+
+        if reference.timestamp == latest.timestamp {
+            return None;
+        }
+
+        let change = latest.temperature - reference.temperature;
+
+        Some(if change >= TREND_THRESHOLD {
+            Trend::Rising
+        } else if change <= -TREND_THRESHOLD {
+            Trend::Falling
+        } else {
+            Trend::Steady
+        })
+    }
+
+    /// Drops samples older than the maximum age. Returns true if anything was removed.
+    fn prune(&mut self, now: DateTime<Utc>) -> bool {
+        let cutoff = (now - self.max_age).timestamp();
+        let before = self.samples.len();
+
+        while self
+            .samples
+            .front()
+            .is_some_and(|sample| sample.timestamp < cutoff)
+        {
+            self.samples.pop_front();
+        }
+
+        self.samples.len() != before
+    }
+
+    /// Rewrites the history file with the current samples.
+    fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        // Write to a temporary file first so that a crash doesn't lose everything
+        let mut temp_path = path.clone().into_os_string();
+        temp_path.push(".tmp");
+
+        let mut file = File::create(&temp_path)?;
+        for sample in &self.samples {
+            writeln!(file, "{}", format_line(sample))?;
+        }
+        file.sync_all()?;
+
+        fs::rename(&temp_path, path)
+    }
+
+    /// Appends a single sample to the history file.
+    fn append(&self, sample: &Sample) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", format_line(sample))
+    }
+}
+
+fn format_line(sample: &Sample) -> String {
+    format!("{},{}", sample.timestamp, sample.temperature)
+}
+
+fn parse_line(line: &str) -> Option<Sample> {
+    let (timestamp, temperature) = line.trim().split_once(',')?;
+
+    Some(Sample {
+        timestamp: timestamp.parse().ok()?,
+        temperature: temperature.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn drops_old_samples() {
+        let now = Utc::now();
+        let mut history = TemperatureHistory::load(None, Duration::hours(24));
+
+        history.record(now - Duration::hours(30), 10.0);
+        history.record(now - Duration::hours(12), 15.0);
+        history.record(now, 20.0);
+
+        assert_eq!(
+            history
+                .since(now - Duration::hours(48))
+                .iter()
+                .map(|x| x.temperature)
+                .collect::<Vec<_>>(),
+            vec![15.0, 20.0]
+        );
+        assert_eq!(history.since(now - Duration::hours(1)).len(), 1);
+    }
+
+    #[test]
+    fn persists_across_loads() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.csv");
+        let now = Utc::now();
+
+        {
+            let mut history = TemperatureHistory::load(Some(path.clone()), Duration::hours(24));
+            history.record(now - Duration::hours(2), 18.5);
+            history.record(now - Duration::hours(1), 19.0);
+            history.record(now, 21.25);
+        }
+
+        let history = TemperatureHistory::load(Some(path.clone()), Duration::hours(24));
+        let samples = history.since(now - Duration::hours(24));
+
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[2].temperature, 21.25);
+        assert_eq!(samples[2].timestamp, now.timestamp());
+    }
+
+    #[test]
+    fn compacts_file_on_load() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.csv");
+        let now = Utc::now();
+
+        let contents = format!(
+            "{},5\nnot a sample\n{},7.5\n",
+            (now - Duration::hours(48)).timestamp(),
+            now.timestamp()
+        );
+        fs::write(&path, contents).unwrap();
+
+        let history = TemperatureHistory::load(Some(path.clone()), Duration::hours(24));
+
+        assert_eq!(history.since(now - Duration::hours(72)).len(), 1);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("{},7.5\n", now.timestamp())
+        );
+    }
+
+    #[test]
+    fn computes_trend() {
+        let now = Utc::now();
+        let mut history = TemperatureHistory::load(None, Duration::hours(24));
+
+        assert_eq!(history.trend(Duration::hours(3)), None);
+
+        history.record(now - Duration::hours(6), 30.0);
+        assert_eq!(history.trend(Duration::hours(3)), None);
+
+        history.record(now - Duration::hours(2), 15.0);
+        assert_eq!(history.trend(Duration::hours(3)), None);
+
+        history.record(now - Duration::hours(1), 15.5);
+        assert_eq!(history.trend(Duration::hours(3)), Some(Trend::Steady));
+
+        history.record(now, 17.0);
+        assert_eq!(history.trend(Duration::hours(3)), Some(Trend::Rising));
+
+        history.record(now + Duration::hours(1), 12.0);
+        assert_eq!(history.trend(Duration::hours(3)), Some(Trend::Falling));
+    }
+}
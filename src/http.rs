@@ -1,18 +1,64 @@
-use std::sync::{mpsc, Arc};
+use std::sync::{mpsc, Arc, Mutex};
 
-use warp::Filter;
+use warp::http::StatusCode;
+use warp::{Filter, Reply};
+
+use crate::clock::ClockFormat;
+use crate::history::{Sample, TemperatureHistory};
+use crate::overrides::Status;
+use crate::schedule::{ExceptionEntry, Schedule, ScheduleException};
 
 use tokio::runtime::{Builder, Runtime};
 
+use chrono::{DateTime, Utc};
+
 use std::net::SocketAddr;
 
+/// Overrides carry when they expire, if ever.
 pub enum RestAPIRequest {
-    SetDay,
-    SetNight,
-    Reset
+    SetDay(Option<DateTime<Utc>>),
+    SetNight(Option<DateTime<Utc>>),
+    SetOff(Option<DateTime<Utc>>),
+    Reset,
+}
+
+/// Query parameters for timed overrides, e.g. `?for=30m` or `?until=07:00`.
+#[derive(Deserialize)]
+struct OverrideQuery {
+    #[serde(rename = "for")]
+    duration: Option<String>,
+    until: Option<String>,
+}
+
+impl OverrideQuery {
+    fn expiry(&self, clock: &ClockFormat) -> Result<Option<DateTime<Utc>>, String> {
+        clock.expiry(self.duration.as_deref(), self.until.as_deref(), Utc::now())
+    }
+}
+
+#[derive(Serialize)]
+struct HistoryResponse {
+    samples: Vec<Sample>,
+}
+
+#[derive(Serialize)]
+struct ExceptionsResponse {
+    exceptions: Vec<ExceptionEntry>,
 }
 
-async fn start(http_endpoint: SocketAddr, sender: mpsc::Sender<RestAPIRequest>) {
+#[derive(Serialize)]
+struct AddExceptionResponse {
+    id: u64,
+}
+
+async fn start(
+    http_endpoint: SocketAddr,
+    sender: mpsc::Sender<RestAPIRequest>,
+    history: Arc<Mutex<TemperatureHistory>>,
+    schedule: Arc<Mutex<Schedule>>,
+    status: Arc<Mutex<Status>>,
+    clock: ClockFormat,
+) {
     let sender_copy = Arc::new(sender);
 
     let reset_sender = sender_copy.clone();
@@ -23,23 +69,122 @@ async fn start(http_endpoint: SocketAddr, sender: mpsc::Sender<RestAPIRequest>)
         "ok"
     });
 
+    let day_clock = clock.clone();
     let day_sender = sender_copy.clone();
-    let day = warp::path!("api" / "day").map(move || {
-        day_sender
-            .send(RestAPIRequest::SetDay)
-            .expect("Failed to set night");
-        "ok"
+    let day = warp::path!("api" / "day")
+        .and(warp::query::<OverrideQuery>())
+        .map(move |query: OverrideQuery| match query.expiry(&day_clock) {
+            Ok(expires) => {
+                day_sender
+                    .send(RestAPIRequest::SetDay(expires))
+                    .expect("Failed to set day");
+                warp::reply::with_status("ok".to_string(), StatusCode::OK)
+            }
+            Err(e) => warp::reply::with_status(e, StatusCode::BAD_REQUEST),
+        });
+
+    let night_clock = clock.clone();
+    let night_sender = sender_copy.clone();
+    let night = warp::path!("api" / "night")
+        .and(warp::query::<OverrideQuery>())
+        .map(
+            move |query: OverrideQuery| match query.expiry(&night_clock) {
+                Ok(expires) => {
+                    night_sender
+                        .send(RestAPIRequest::SetNight(expires))
+                        .expect("Failed to set night");
+                    warp::reply::with_status("ok".to_string(), StatusCode::OK)
+                }
+                Err(e) => warp::reply::with_status(e, StatusCode::BAD_REQUEST),
+            },
+        );
+
+    let off_clock = clock;
+    let off_sender = sender_copy;
+    let off = warp::path!("api" / "off")
+        .and(warp::query::<OverrideQuery>())
+        .map(move |query: OverrideQuery| match query.expiry(&off_clock) {
+            Ok(expires) => {
+                off_sender
+                    .send(RestAPIRequest::SetOff(expires))
+                    .expect("Failed to set off");
+                warp::reply::with_status("ok".to_string(), StatusCode::OK)
+            }
+            Err(e) => warp::reply::with_status(e, StatusCode::BAD_REQUEST),
+        });
+
+    let status = warp::path!("api" / "status").and(warp::get()).map(move || {
+        let status = status
+            .lock()
+            .expect("Failed to lock display status")
+            .clone();
+        warp::reply::json(&status)
     });
 
-    let night_sender = sender_copy;
-    let night = warp::path!("api" / "night").map(move || {
-        night_sender
-            .send(RestAPIRequest::SetNight)
-            .expect("Failed to set night");
-        "ok"
-    });
+    let history = warp::path!("api" / "weather" / "history")
+        .and(warp::get())
+        .map(move || {
+            let samples = history
+                .lock()
+                .expect("Failed to lock temperature history")
+                .all();
+            warp::reply::json(&HistoryResponse { samples })
+        });
+
+    let list_schedule = schedule.clone();
+    let list_exceptions = warp::path!("api" / "schedule" / "exceptions")
+        .and(warp::get())
+        .map(move || {
+            let exceptions = list_schedule
+                .lock()
+                .expect("Failed to lock sleep schedule")
+                .exceptions()
+                .to_vec();
+            warp::reply::json(&ExceptionsResponse { exceptions })
+        });
+
+    let add_schedule = schedule.clone();
+    let add_exception = warp::path!("api" / "schedule" / "exceptions")
+        .and(warp::post())
+        .and(warp::body::json())
+        .map(move |exception: ScheduleException| {
+            let mut schedule = add_schedule.lock().expect("Failed to lock sleep schedule");
+
+            // Loading these from the config fails too, rather than never applying them
+            if exception.is_sun_relative() && !schedule.has_location() {
+                return warp::reply::with_status(
+                    "sleep windows relative to the sun need a latitude and longitude",
+                    StatusCode::BAD_REQUEST,
+                )
+                .into_response();
+            }
+
+            let id = schedule.add_exception(exception);
+            warp::reply::json(&AddExceptionResponse { id }).into_response()
+        });
+
+    let remove_exception = warp::path!("api" / "schedule" / "exceptions" / u64)
+        .and(warp::delete())
+        .map(move |id| {
+            let removed = schedule
+                .lock()
+                .expect("Failed to lock sleep schedule")
+                .remove_exception(id);
+
+            if removed {
+                warp::reply::with_status("ok", StatusCode::OK)
+            } else {
+                warp::reply::with_status("no such exception", StatusCode::NOT_FOUND)
+            }
+        });
 
-    let api = reset.or(day.or(night));
+    let api = reset
+        .or(day.or(night).or(off))
+        .or(status)
+        .or(history)
+        .or(list_exceptions)
+        .or(add_exception)
+        .or(remove_exception);
 
     warp::serve(api).run(http_endpoint).await;
 }
@@ -60,7 +205,13 @@ impl RestAPI {
         }
     }
 
-    pub fn start(http_endpoint: &str) -> Self {
+    pub fn start(
+        http_endpoint: &str,
+        history: Arc<Mutex<TemperatureHistory>>,
+        schedule: Arc<Mutex<Schedule>>,
+        status: Arc<Mutex<Status>>,
+        clock: ClockFormat,
+    ) -> Self {
         let runtime = Builder::new_multi_thread()
             .worker_threads(1)
             .enable_all()
@@ -80,6 +231,10 @@ impl RestAPI {
                 .parse::<SocketAddr>()
                 .expect("Failed to parse socket address"),
             request_tx,
+            history,
+            schedule,
+            status,
+            clock,
         ));
 
         api
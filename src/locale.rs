@@ -0,0 +1,371 @@
+//! Translations of day and month names, and text shown on screen.
+
+use leaffront_weather::UvCategory;
+
+/// Languages that on-screen text can be shown in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    English,
+    German,
+    French,
+    Spanish,
+    Italian,
+    Dutch,
+}
+
+/// Everything that gets translated for a language.
+pub struct Strings {
+    /// Monday first.
+    pub days: [&'static str; 7],
+    pub short_days: [&'static str; 7],
+    pub months: [&'static str; 12],
+    pub short_months: [&'static str; 12],
+    /// Date format used under the clock unless one is configured.
+    pub date_format: &'static str,
+    /// Suffix for a day of the month, as `%o` in date formats.
+    pub ordinal: fn(u32) -> &'static str,
+    pub unavailable: &'static str,
+    pub sunrise: &'static str,
+    pub sunset: &'static str,
+    pub uv: &'static str,
+    /// Indexed by `UvCategory`, lowest first.
+    pub uv_categories: [&'static str; 5],
+    /// Forecast temperatures.
+    pub high: &'static str,
+    pub low: &'static str,
+    pub today: &'static str,
+    pub tomorrow: &'static str,
+    pub history_title: &'static str,
+    pub no_history: &'static str,
+}
+
+fn english_ordinal(day: u32) -> &'static str {
+    match day {
+        1 | 21 | 31 => "st",
+        2 | 22 => "nd",
+        3 | 23 => "rd",
+        _ => "th",
+    }
+}
+
+fn german_ordinal(_day: u32) -> &'static str {
+    "."
+}
+
+fn french_ordinal(day: u32) -> &'static str {
+    match day {
+        1 => "er",
+        _ => "",
+    }
+}
+
+fn no_ordinal(_day: u32) -> &'static str {
+    ""
+}
+
+static ENGLISH: Strings = Strings {
+    days: [
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+        "Sunday",
+    ],
+    short_days: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    months: [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+    short_months: [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ],
+    date_format: "%A, %-d%o of %B",
+    ordinal: english_ordinal,
+    unavailable: "unavailable",
+    sunrise: "Sunrise",
+    sunset: "Sunset",
+    uv: "UV",
+    uv_categories: ["Low", "Moderate", "High", "Very high", "Extreme"],
+    high: "High",
+    low: "Low",
+    today: "Today",
+    tomorrow: "Tomorrow",
+    history_title: "Last 24 hours",
+    no_history: "No history recorded yet",
+};
+
+static GERMAN: Strings = Strings {
+    days: [
+        "Montag",
+        "Dienstag",
+        "Mittwoch",
+        "Donnerstag",
+        "Freitag",
+        "Samstag",
+        "Sonntag",
+    ],
+    short_days: ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+    months: [
+        "Januar",
+        "Februar",
+        "März",
+        "April",
+        "Mai",
+        "Juni",
+        "Juli",
+        "August",
+        "September",
+        "Oktober",
+        "November",
+        "Dezember",
+    ],
+    short_months: [
+        "Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez",
+    ],
+    date_format: "%A, %-d%o %B",
+    ordinal: german_ordinal,
+    unavailable: "nicht verfügbar",
+    sunrise: "Sonnenaufgang",
+    sunset: "Sonnenuntergang",
+    uv: "UV",
+    uv_categories: ["Niedrig", "Mäßig", "Hoch", "Sehr hoch", "Extrem"],
+    high: "Höchst",
+    low: "Tiefst",
+    today: "Heute",
+    tomorrow: "Morgen",
+    history_title: "Letzte 24 Stunden",
+    no_history: "Noch keine Werte aufgezeichnet",
+};
+
+static FRENCH: Strings = Strings {
+    days: [
+        "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+    ],
+    short_days: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+    months: [
+        "janvier",
+        "février",
+        "mars",
+        "avril",
+        "mai",
+        "juin",
+        "juillet",
+        "août",
+        "septembre",
+        "octobre",
+        "novembre",
+        "décembre",
+    ],
+    short_months: [
+        "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.",
+        "déc.",
+    ],
+    date_format: "%A %-d%o %B",
+    ordinal: french_ordinal,
+    unavailable: "indisponible",
+    sunrise: "Lever du soleil",
+    sunset: "Coucher du soleil",
+    uv: "UV",
+    uv_categories: ["Faible", "Modéré", "Élevé", "Très élevé", "Extrême"],
+    high: "Max",
+    low: "Min",
+    today: "Aujourd'hui",
+    tomorrow: "Demain",
+    history_title: "Dernières 24 heures",
+    no_history: "Aucun historique enregistré",
+};
+
+static SPANISH: Strings = Strings {
+    days: [
+        "lunes",
+        "martes",
+        "miércoles",
+        "jueves",
+        "viernes",
+        "sábado",
+        "domingo",
+    ],
+    short_days: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+    months: [
+        "enero",
+        "febrero",
+        "marzo",
+        "abril",
+        "mayo",
+        "junio",
+        "julio",
+        "agosto",
+        "septiembre",
+        "octubre",
+        "noviembre",
+        "diciembre",
+    ],
+    short_months: [
+        "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+    ],
+    date_format: "%A, %-d de %B",
+    ordinal: no_ordinal,
+    unavailable: "no disponible",
+    sunrise: "Amanecer",
+    sunset: "Atardecer",
+    uv: "UV",
+    uv_categories: ["Bajo", "Moderado", "Alto", "Muy alto", "Extremo"],
+    high: "Máx",
+    low: "Mín",
+    today: "Hoy",
+    tomorrow: "Mañana",
+    history_title: "Últimas 24 horas",
+    no_history: "Aún no hay historial",
+};
+
+static ITALIAN: Strings = Strings {
+    days: [
+        "lunedì",
+        "martedì",
+        "mercoledì",
+        "giovedì",
+        "venerdì",
+        "sabato",
+        "domenica",
+    ],
+    short_days: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+    months: [
+        "gennaio",
+        "febbraio",
+        "marzo",
+        "aprile",
+        "maggio",
+        "giugno",
+        "luglio",
+        "agosto",
+        "settembre",
+        "ottobre",
+        "novembre",
+        "dicembre",
+    ],
+    short_months: [
+        "gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic",
+    ],
+    date_format: "%A %-d %B",
+    ordinal: no_ordinal,
+    unavailable: "non disponibile",
+    sunrise: "Alba",
+    sunset: "Tramonto",
+    uv: "UV",
+    uv_categories: ["Basso", "Moderato", "Alto", "Molto alto", "Estremo"],
+    high: "Max",
+    low: "Min",
+    today: "Oggi",
+    tomorrow: "Domani",
+    history_title: "Ultime 24 ore",
+    no_history: "Nessuno storico registrato",
+};
+
+static DUTCH: Strings = Strings {
+    days: [
+        "maandag",
+        "dinsdag",
+        "woensdag",
+        "donderdag",
+        "vrijdag",
+        "zaterdag",
+        "zondag",
+    ],
+    short_days: ["ma", "di", "wo", "do", "vr", "za", "zo"],
+    months: [
+        "januari",
+        "februari",
+        "maart",
+        "april",
+        "mei",
+        "juni",
+        "juli",
+        "augustus",
+        "september",
+        "oktober",
+        "november",
+        "december",
+    ],
+    short_months: [
+        "jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec",
+    ],
+    date_format: "%A %-d %B",
+    ordinal: no_ordinal,
+    unavailable: "niet beschikbaar",
+    sunrise: "Zonsopgang",
+    sunset: "Zonsondergang",
+    uv: "UV",
+    uv_categories: ["Laag", "Matig", "Hoog", "Zeer hoog", "Extreem"],
+    high: "Max",
+    low: "Min",
+    today: "Vandaag",
+    tomorrow: "Morgen",
+    history_title: "Afgelopen 24 uur",
+    no_history: "Nog geen geschiedenis opgeslagen",
+};
+
+impl Locale {
+    /// Parses a language tag like "de-DE" or "fr_CA". Only the language is used.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_']).next()?;
+
+        match language.to_lowercase().as_str() {
+            "en" => Some(Locale::English),
+            "de" => Some(Locale::German),
+            "fr" => Some(Locale::French),
+            "es" => Some(Locale::Spanish),
+            "it" => Some(Locale::Italian),
+            "nl" => Some(Locale::Dutch),
+            _ => None,
+        }
+    }
+
+    pub fn strings(self) -> &'static Strings {
+        match self {
+            Locale::English => &ENGLISH,
+            Locale::German => &GERMAN,
+            Locale::French => &FRENCH,
+            Locale::Spanish => &SPANISH,
+            Locale::Italian => &ITALIAN,
+            Locale::Dutch => &DUTCH,
+        }
+    }
+
+    pub fn uv_category(self, category: UvCategory) -> &'static str {
+        let index = match category {
+            UvCategory::Low => 0,
+            UvCategory::Moderate => 1,
+            UvCategory::High => 2,
+            UvCategory::VeryHigh => 3,
+            UvCategory::Extreme => 4,
+        };
+
+        self.strings().uv_categories[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tags() {
+        assert_eq!(Locale::from_tag("de-DE"), Some(Locale::German));
+        assert_eq!(Locale::from_tag("fr_CA"), Some(Locale::French));
+        assert_eq!(Locale::from_tag("EN"), Some(Locale::English));
+        assert_eq!(Locale::from_tag("xx-YY"), None);
+    }
+}
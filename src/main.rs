@@ -24,6 +24,7 @@ extern crate clap;
 extern crate image;
 
 extern crate chrono;
+extern crate chrono_tz;
 extern crate rand;
 
 extern crate ctrlc;
@@ -34,18 +35,36 @@ extern crate log;
 mod config;
 mod state;
 
+mod ambient;
 mod background;
 mod clock;
+mod dimmer;
+mod filter;
+mod fonts;
+mod graph;
+mod history;
+mod locale;
 mod main_loop;
+mod overrides;
+mod power;
+mod schedule;
+mod subtitle;
+mod sun;
+mod theme;
 
 mod platform;
 
 mod http;
 
+mod weather_cmd;
+
 use clap::{Arg, Command};
 
 use env_logger::Env;
 use leaffront_core::version::VersionInfo;
+use leaffront_weather::WeatherProviderKind;
+
+use std::process;
 
 use platform::*;
 
@@ -82,6 +101,32 @@ fn main() {
                 .help("Shows version information and exits.")
                 .required(false),
         )
+        .subcommand(
+            Command::new("weather")
+                .about("Checks the weather configuration without starting the UI")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("check")
+                        .about("Fetches weather once from each configured provider"),
+                )
+                .subcommand(
+                    Command::new("search")
+                        .about("Lists locations matching some text, for use in the config")
+                        .arg(
+                            Arg::new("kind")
+                                .short('k')
+                                .long("kind")
+                                .help("Provider to search (defaults to the first configured)")
+                                .possible_values(["BOM", "OpenWeatherMap"])
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::new("text")
+                                .help("Place name or postcode to search for")
+                                .required(true),
+                        ),
+                ),
+        )
         .get_matches();
 
     if matches.is_present("version") {
@@ -98,5 +143,23 @@ fn main() {
 
     let config = config::load_config(config_file.into());
 
+    if let Some(("weather", matches)) = matches.subcommand() {
+        let success = match matches.subcommand() {
+            Some(("check", _)) => weather_cmd::check(&config),
+            Some(("search", matches)) => {
+                let kind = matches.value_of("kind").map(|x| match x {
+                    "OpenWeatherMap" => WeatherProviderKind::OpenWeatherMap,
+                    _ => WeatherProviderKind::BOM,
+                });
+                let text = matches.value_of("text").expect("Search text is required");
+
+                weather_cmd::search(&config, kind, text)
+            }
+            _ => unreachable!("A weather subcommand is required"),
+        };
+
+        process::exit(if success { 0 } else { 1 });
+    }
+
     main_loop::main_loop(config);
 }
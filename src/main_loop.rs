@@ -6,42 +6,70 @@ use leaffront_core::render::texture::Texture;
 use leaffront_core::render::Drawer;
 
 use leaffront_weather::manager::WeatherManager;
+use leaffront_weather::{WarningSeverity, WeatherWarning};
 
+use crate::ambient::AmbientLight;
 use crate::background::manager::BackgroundManager;
+use crate::clock::NextChange;
+
+use crate::graph::{history_graph, sparkline, trend_arrow};
+use crate::history::TemperatureHistory;
 
 use crate::http::RestAPI;
 use crate::http::RestAPIRequest;
+use crate::overrides::{DisplayState, Override, OverrideStore, Status};
 use crate::state::DisplayNotification;
-use crate::state::Message;
 use crate::state::ScreenState;
-
-use crate::clock::check_night;
+use crate::subtitle::{upcoming_event, Widget, CALENDAR_DAYS};
+use crate::theme::apply_style;
 
 use crate::config::LeaffrontConfig;
+use crate::dimmer::{approach, Dimmer, FADE_INTERVAL};
 
 use crate::platform::*;
 
 use chrono::Datelike;
 use chrono::Local;
+use chrono::Utc;
 
 use rand::thread_rng;
 use rand::Rng;
 
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::time::Duration;
 use std::time::Instant;
 
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use ctrlc;
 
 use egui::epaint::Primitive;
-use egui::style::Margin;
 use egui::ClippedPrimitive;
 use egui::TextureId;
 use egui::TexturesDelta;
-use egui::{Align2, Color32, Event, Frame, PointerButton, Pos2};
+use egui::{Align2, Color32, Event, PointerButton, Pos2, Vec2};
+
+/// How long the temperature history page stays open without interaction.
+static HISTORY_PAGE_SECS: u64 = 30;
+
+/// Returns the state the schedule and any override call for.
+fn wanted_state(forced: Option<DisplayState>, is_night: bool, is_off: bool) -> DisplayState {
+    match forced {
+        Some(forced) => forced,
+        None if is_off => DisplayState::Off,
+        None if is_night => DisplayState::Night,
+        None => DisplayState::Day,
+    }
+}
+
+/// Returns true if someone being in the room should keep the display awake.
+/// Overrides always win over presence.
+fn held_by_presence(forced: Option<DisplayState>, away: Option<bool>) -> bool {
+    forced.is_none() && away == Some(false)
+}
 
 /// A texture bundle contains both a raw, CPU-managed texture, as well
 /// as a GPU texture. This allows for updates to the CPU-managed texture
@@ -52,8 +80,7 @@ struct TextureBundle {
 }
 
 pub fn main_loop(config: LeaffrontConfig) {
-    let start_night = config.sleep.sleep_hour;
-    let end_night = config.sleep.wakeup_hour;
+    let schedule = Arc::new(Mutex::new(config.sleep.get_schedule()));
 
     // Connect to the backend
     let mut backend = BackendImpl::new().unwrap();
@@ -66,41 +93,128 @@ pub fn main_loop(config: LeaffrontConfig) {
     // Startup input handling
     let input = InputImpl::new();
 
+    let mut overrides = OverrideStore::load(config.sleep.override_file.as_ref().map(PathBuf::from));
+
+    let locale = config.get_locale();
+    let strings = locale.strings();
+    let clock = config.clock.get_format(locale);
+
     // Check the startup time
-    let mut state = if check_night(start_night, end_night) {
-        ScreenState::Night
-    } else {
-        ScreenState::Day(Message::Date)
+    let wanted = {
+        let schedule = schedule.lock().expect("Failed to lock sleep schedule");
+        wanted_state(
+            overrides.active(Utc::now()).map(|x| x.state),
+            clock.is_night(&schedule, Utc::now()),
+            clock.is_off(&schedule, Utc::now()),
+        )
     };
+    let mut state = match wanted {
+        DisplayState::Day => ScreenState::Day(None),
+        DisplayState::Night => ScreenState::Night,
+        DisplayState::Off => ScreenState::Off,
+    };
+
+    let status = Arc::new(Mutex::new(Status {
+        state: state.display_state(),
+        forced: overrides.active(Utc::now()).cloned(),
+    }));
+
+    let history = Arc::new(Mutex::new(TemperatureHistory::load(
+        config.weather.history_file.as_ref().map(PathBuf::from),
+        chrono::Duration::hours(config.weather.history_hours.unwrap_or(24) as i64),
+    )));
+    let mut history_opened: Option<Instant> = None;
 
     let http_server = match &config.sleep.http_endpoint {
-        Some(addr) => Some(RestAPI::start(addr)),
+        Some(addr) => Some(RestAPI::start(
+            addr,
+            history.clone(),
+            schedule.clone(),
+            status.clone(),
+            clock.clone(),
+        )),
         None => None,
     };
-    let mut http_forced: Option<ScreenState> = None;
 
+    let mut control = config.brightness.open_control();
+    let mut control_failed = false;
+
+    // Fade in from wherever the backlight was left
     let brightness = match state {
         ScreenState::Day(_) => config.day.brightness,
-        ScreenState::Night => config.night.brightness,
+        ScreenState::Night | ScreenState::Off => config.night.brightness,
     };
-    match drawer.set_brightness(brightness) {
-        Err(v) => warn!("Failed to set brightness: {:?}", v),
-        _ => {}
+    let current = control.as_mut().and_then(|x| x.get_percent().ok());
+    let mut dimmer = Dimmer::new(
+        current.unwrap_or(brightness) as f32,
+        config.brightness.curve,
+    );
+    let fade = config.brightness.get_fade();
+    dimmer.fade_to(brightness as f32, fade, Instant::now());
+
+    let filter_settings = config.filter.get_settings();
+    let mut filter = None;
+
+    // The brightness ramps and the filter both look ahead to the next schedule change
+    let mut next_change = NextChange::new(
+        config
+            .brightness
+            .get_dusk()
+            .max(config.brightness.get_dawn())
+            .max(filter_settings.warm),
+    );
+
+    let mut ambient = config.ambient.as_ref().and_then(|ambient| {
+        ambient
+            .open_sensor()
+            .map(|sensor| AmbientLight::new(sensor, ambient.get_settings()))
+    });
+    let mut last_ambient_level = None;
+
+    let power = config.power.get_control();
+    let off_after = config.power.get_off_after();
+    let mut last_activity = Instant::now();
+
+    #[cfg(target_os = "linux")]
+    let mut presence = config.presence.as_ref().and_then(|x| x.open_sensor());
+    #[cfg(target_os = "linux")]
+    let presence_idle = config
+        .presence
+        .as_ref()
+        .map(|x| x.get_idle())
+        .unwrap_or_default();
+    #[cfg(not(target_os = "linux"))]
+    if config.presence.is_some() {
+        warn!("Presence sensors are only supported on Linux");
     }
+    let off_when_away = config.presence.as_ref().is_some_and(|x| x.off_when_away);
 
     let mut state_countdown = Instant::now();
 
-    //let font_data = include_bytes!("../res/Lato-Regular.ttf");
+    let weather_locations = config.get_weather_locations();
+    if weather_locations.is_empty() {
+        warn!("No weather locations configured");
+    }
+
+    let subtitles = config.day.get_subtitles(weather_locations.len());
 
-    let mut weather_manager = WeatherManager::new(
-        config.weather.update_freq * 60 * 1000,
-        config.weather.kind,
-        config.weather.config.clone(),
-    );
+    let mut weather_managers: Vec<(Option<String>, WeatherManager)> = weather_locations
+        .into_iter()
+        .map(|location| {
+            let manager = WeatherManager::new(
+                config.weather.update_freq * 60 * 1000,
+                location.get_providers(),
+                config.weather.get_retry_policy(),
+            );
+            (location.name, manager)
+        })
+        .collect();
+
+    let mut known_warnings: HashSet<String> = HashSet::new();
 
     let mut rng = thread_rng();
-    let mut night_x = -1f32;
-    let mut night_y = -1f32;
+    let mut night_x = 0f32;
+    let mut night_y = 0f32;
     let mut night_cooldown = Instant::now();
 
     // Update the background
@@ -126,31 +240,33 @@ pub fn main_loop(config: LeaffrontConfig) {
     .expect("Error setting Ctrl-C handler");
 
     let egui_ctx = egui::Context::default();
+    egui_ctx.set_fonts(config.fonts.get_definitions());
 
     let mut style = egui_ctx.style().as_ref().to_owned();
-    style.spacing.window_margin = Margin::symmetric(15.0, 15.0);
-    style.visuals.dark_mode = true;
-    style.visuals.widgets.noninteractive.bg_fill = Color32::from_rgba_unmultiplied(20, 20, 20, 220);
-    style.visuals.widgets.noninteractive.bg_stroke.color =
-        Color32::from_rgba_unmultiplied(20, 20, 20, 220);
-    style.visuals.widgets.noninteractive.fg_stroke.color = Color32::WHITE;
-    style
-        .text_styles
-        .get_mut(&egui::TextStyle::Heading)
-        .expect("Heading text style should exist")
-        .size += 20.0;
+    apply_style(
+        &mut style,
+        &config.theme.get_style(),
+        config.theme.get_body_size(),
+    );
     egui_ctx.set_style(style);
 
+    let panels = config.get_panels();
+
     let start_time = Instant::now();
 
     let mut egui_textures: HashMap<TextureId, TextureBundle> = HashMap::new();
 
     drawer.set_fullscreen(config.fullscreen);
 
+    if state == ScreenState::Off {
+        if let Err(e) = power.set(false, &mut control, &mut drawer) {
+            warn!("Failed to turn the display off: {:?}", e);
+        }
+    }
+
     let mut textures_delta = TexturesDelta::default();
 
     let mut last_second = Local::now();
-    let mut last_set_brightness = None;
 
     let mut pointer_event: Option<Event> = None;
 
@@ -158,6 +274,13 @@ pub fn main_loop(config: LeaffrontConfig) {
 
     input.run(drawer, move |input, drawer| {
         if !running.load(Ordering::SeqCst) || !input.do_continue() {
+            // Don't leave the panel dark after exiting
+            if state == ScreenState::Off {
+                if let Err(e) = power.set(true, &mut control, drawer) {
+                    warn!("Failed to turn the display on: {:?}", e);
+                }
+            }
+
             return (false, Instant::now());
         }
 
@@ -208,6 +331,45 @@ pub fn main_loop(config: LeaffrontConfig) {
         // TODO: Config time
         notifications.retain(|x| x.displayed.elapsed() < Duration::from_secs(5));
 
+        // Check for new or withdrawn weather warnings, naming the location if there
+        // is more than one
+        let mut warnings: Vec<(Option<String>, WeatherWarning)> = Vec::new();
+        let multiple_locations = weather_managers.len() > 1;
+        for (name, manager) in weather_managers.iter_mut() {
+            for warning in manager.get_warnings() {
+                // Nearby locations often share warnings, so only show each one once
+                if warnings.iter().any(|(_, x)| x.id == warning.id) {
+                    continue;
+                }
+
+                let name = if multiple_locations {
+                    name.clone()
+                } else {
+                    None
+                };
+                warnings.push((name, warning.clone()));
+            }
+        }
+
+        let new_warning = warnings
+            .iter()
+            .any(|(_, x)| !known_warnings.contains(&x.id));
+        if new_warning || warnings.len() != known_warnings.len() {
+            known_warnings = warnings.iter().map(|(_, x)| x.id.clone()).collect();
+            dirty_state = true;
+        }
+
+        // Record new temperatures for the first location
+        if let Some((_, manager)) = weather_managers.first_mut() {
+            if let Some(weather) = manager.take_update() {
+                history
+                    .lock()
+                    .expect("Failed to lock temperature history")
+                    .record(Utc::now(), weather.temperature);
+                dirty_state = true;
+            }
+        }
+
         // Handle the adjustment of state
         let touched = input.is_mouse_down();
 
@@ -219,15 +381,83 @@ pub fn main_loop(config: LeaffrontConfig) {
         // Listen to HTTP events if required
         if let Some(server) = http_server.as_ref() {
             match server.read_message() {
-                Some(RestAPIRequest::SetDay) => http_forced = Some(ScreenState::Day(Message::Date)),
-                Some(RestAPIRequest::SetNight) => http_forced = Some(ScreenState::Night),
-                Some(RestAPIRequest::Reset) => http_forced = None,
+                Some(RestAPIRequest::SetDay(expires)) => overrides.set(Some(Override {
+                    state: DisplayState::Day,
+                    expires,
+                })),
+                Some(RestAPIRequest::SetNight(expires)) => overrides.set(Some(Override {
+                    state: DisplayState::Night,
+                    expires,
+                })),
+                Some(RestAPIRequest::SetOff(expires)) => overrides.set(Some(Override {
+                    state: DisplayState::Off,
+                    expires,
+                })),
+                Some(RestAPIRequest::Reset) => overrides.set(None),
                 None => {}
             }
         }
 
+        let forced = overrides.active(Utc::now()).map(|x| x.state);
+
+        let (is_night, is_off) = {
+            let schedule = schedule.lock().expect("Failed to lock sleep schedule");
+            (
+                clock.is_night(&schedule, Utc::now()),
+                clock.is_off(&schedule, Utc::now()),
+            )
+        };
+        let mut wanted = wanted_state(forced, is_night, is_off);
+
+        // Someone arriving wakes the display like a touch, and it stays awake until
+        // they've been gone for a while
+        #[cfg(target_os = "linux")]
+        let (arrived, away) = (
+            presence.as_mut().is_some_and(|x| x.update(Instant::now())),
+            presence
+                .as_ref()
+                .map(|x| x.idle_for(Instant::now()) >= presence_idle),
+        );
+        #[cfg(not(target_os = "linux"))]
+        let (arrived, away) = (false, None);
+
+        if forced.is_none() && off_when_away && away == Some(true) && wanted == DisplayState::Day {
+            wanted = DisplayState::Off;
+        }
+
+        // Inactivity only counts once the display has gone to sleep
+        if touched || arrived || state.display_state() == DisplayState::Day {
+            last_activity = Instant::now();
+        }
+        let inactive = forced.is_none() && off_after.is_some_and(|x| last_activity.elapsed() >= x);
+
+        // Skip subtitles which have nothing to show
+        let local_now = clock.naive_local(Utc::now());
+        let today = weather_managers
+            .first_mut()
+            .and_then(|(_, manager)| manager.get().ok())
+            .and_then(|x| x.daily.first().cloned());
+        let available = |widget: &Widget| match widget {
+            Widget::Forecast => today
+                .as_ref()
+                .is_some_and(|x| x.temp_min.is_some() || x.temp_max.is_some()),
+            Widget::Astronomy => today
+                .as_ref()
+                .is_some_and(|x| x.sunrise.is_some() || x.sunset.is_some() || x.uv.is_some()),
+            Widget::Calendar { within_days } => upcoming_event(
+                schedule
+                    .lock()
+                    .expect("Failed to lock sleep schedule")
+                    .exceptions(),
+                local_now.date(),
+                within_days.unwrap_or(CALENDAR_DAYS),
+            )
+            .is_some(),
+            _ => true,
+        };
+
         let next_state = match &state {
-            &ScreenState::Day(ref msg) => {
+            &ScreenState::Day(current) => {
                 if touched {
                     night_cooldown = Instant::now();
                 }
@@ -238,51 +468,188 @@ pub fn main_loop(config: LeaffrontConfig) {
                 }
 
                 if night_cooldown.elapsed() > Duration::from_secs(config.night.night_tap_cooldown)
-                    && ((check_night(start_night, end_night) && http_forced == None)
-                        || http_forced == Some(ScreenState::Night))
+                    && wanted != DisplayState::Day
+                    && !held_by_presence(forced, away)
                 {
                     state_countdown = Instant::now();
-                    Some(ScreenState::Night)
-                } else if state_countdown.elapsed() > Duration::from_secs(config.day.subtitle_secs)
+                    Some(if wanted == DisplayState::Off {
+                        ScreenState::Off
+                    } else {
+                        ScreenState::Night
+                    })
+                } else if state_countdown.elapsed()
+                    > current
+                        .and_then(|i| subtitles.get(i))
+                        .map_or(Duration::from_secs(config.day.subtitle_secs), |x| {
+                            x.duration
+                        })
                 {
                     state_countdown = Instant::now();
-                    Some(ScreenState::Day(msg.next()))
+
+                    Some(ScreenState::Day(
+                        subtitles.next(current, local_now, available),
+                    ))
                 } else {
                     None
                 }
             }
             &ScreenState::Night => {
-                if touched {
+                if touched || arrived || (new_warning && config.weather.wake_on_warning) {
                     night_cooldown = Instant::now();
-                    Some(ScreenState::Day(Message::Date))
-                } else if (!check_night(start_night, end_night) && http_forced == None) || http_forced == Some(ScreenState::Day(Message::Date)) {
-                    Some(ScreenState::Day(Message::Date))
+                    Some(ScreenState::Day(None))
+                } else if wanted == DisplayState::Day {
+                    Some(ScreenState::Day(None))
+                } else if wanted == DisplayState::Off || inactive {
+                    Some(ScreenState::Off)
+                } else {
+                    None
+                }
+            }
+            &ScreenState::Off => {
+                if touched || arrived || (new_warning && config.weather.wake_on_warning) {
+                    night_cooldown = Instant::now();
+                    Some(ScreenState::Day(None))
+                } else if wanted == DisplayState::Day {
+                    Some(ScreenState::Day(None))
+                } else if wanted == DisplayState::Night && !inactive {
+                    state_countdown = Instant::now();
+                    Some(ScreenState::Night)
                 } else {
                     None
                 }
             }
         };
 
+        let previous_state = state.display_state();
+
         match next_state {
             Some(next) => {
                 state = next;
                 dirty_state = true;
+            }
+            None => {}
+        }
+
+        // Start on the first subtitle when waking up
+        if state == ScreenState::Day(None) {
+            let first = subtitles.next(None, local_now, available);
+            if first.is_some() {
+                state = ScreenState::Day(first);
+                dirty_state = true;
+            }
+        }
 
-                // Configure brightness (if required)
-                let brightness = match state {
-                    ScreenState::Day(_) => config.day.brightness,
-                    ScreenState::Night => config.night.brightness,
-                };
-                if last_set_brightness != Some(brightness) {
-                    match drawer.set_brightness(brightness) {
-                        Err(v) => warn!("Failed to set brightness: {:?}", v),
-                        _ => {}
-                    }
+        let powered = state != ScreenState::Off;
+        if powered != (previous_state != DisplayState::Off) {
+            info!("Turning the display {}", if powered { "on" } else { "off" });
 
-                    last_set_brightness = Some(brightness);
+            if let Err(e) = power.set(powered, &mut control, drawer) {
+                warn!(
+                    "Failed to turn the display {}: {:?}",
+                    if powered { "on" } else { "off" },
+                    e
+                );
+            }
+        }
+
+        // Work out the brightness, dimming towards the next scheduled change if it's close
+        let (brightness, other_brightness, ramp) = match state {
+            ScreenState::Day(_) => (
+                config.day.brightness,
+                config.night.brightness,
+                config.brightness.get_dusk(),
+            ),
+            ScreenState::Night => (
+                config.night.brightness,
+                config.day.brightness,
+                config.brightness.get_dawn(),
+            ),
+            // Ready for waking up, without ramping towards anything
+            ScreenState::Off => (
+                config.night.brightness,
+                config.night.brightness,
+                chrono::Duration::zero(),
+            ),
+        };
+
+        // Only ramp towards changes the schedule is actually going to make
+        let until_change = if forced.is_none() && is_night == (state == ScreenState::Night) {
+            next_change.until(
+                &clock,
+                &schedule.lock().expect("Failed to lock sleep schedule"),
+                Utc::now(),
+                ramp,
+            )
+        } else {
+            None
+        };
+
+        let mut brightness = approach(
+            brightness,
+            other_brightness,
+            until_change,
+            ramp,
+            config.brightness.curve,
+        );
+
+        // A dark room dims the display further, whatever the time
+        let ambient_level = ambient.as_mut().and_then(|x| x.update(Instant::now()));
+        if let Some(level) = ambient_level {
+            brightness = brightness.min(level);
+        }
+
+        // Ambient light changes in steps, so fade those too
+        if state.display_state() != previous_state || ambient_level != last_ambient_level {
+            last_ambient_level = ambient_level;
+            dimmer.fade_to(brightness, fade, Instant::now());
+        } else {
+            dimmer.retarget(brightness);
+        }
+
+        let level = dimmer.update(Instant::now());
+        if let (Some(control), Some(level)) = (&mut control, level) {
+            match control.set_percent(level) {
+                // Only warn once, rather than on every step of a fade
+                Err(v) if !control_failed => {
+                    warn!("Failed to set brightness: {:?}", v);
+                    control_failed = true;
                 }
+                Err(_) => {}
+                Ok(()) => control_failed = false,
             }
-            None => {}
+        }
+
+        {
+            let mut status = status.lock().expect("Failed to lock display status");
+            status.state = state.display_state();
+            status.forced = overrides.active(Utc::now()).cloned();
+        }
+
+        // Nothing is drawn until something wakes the display
+        if state == ScreenState::Off {
+            history_opened = None;
+            return (
+                true,
+                Instant::now() + Duration::from_millis(config.refresh_rate),
+            );
+        }
+
+        // Warm the frame up towards bedtime, or tint it at night
+        let until_sleep = if forced.is_none() && !is_night {
+            next_change.until(
+                &clock,
+                &schedule.lock().expect("Failed to lock sleep schedule"),
+                Utc::now(),
+                filter_settings.warm,
+            )
+        } else {
+            None
+        };
+        let next_filter = filter_settings.color(state == ScreenState::Night, until_sleep);
+        if filter.as_ref() != Some(&next_filter) {
+            drawer.set_color_filter(&next_filter);
+            filter = Some(next_filter);
+            dirty_state = true;
         }
 
         // Make sure egui recognises external updates
@@ -296,48 +663,171 @@ pub fn main_loop(config: LeaffrontConfig) {
         let screen_height = drawer.get_height();
 
         match &state {
-            &ScreenState::Day(ref subtitle) => {
-                let datetime = Local::now();
+            &ScreenState::Day(subtitle) => {
+                let now = Utc::now();
 
                 egui::Window::new("Day Display")
                     .enabled(true)
                     .resizable(false)
-                    .anchor(Align2::LEFT_BOTTOM, (10.0, -10.0))
+                    .anchor(panels.day.anchor, panels.day.offset)
                     .auto_sized()
                     .min_width(100.0)
                     .min_height(70.0)
                     .collapsible(false)
                     .title_bar(false)
+                    .frame(panels.day.frame(&egui_ctx.style()))
                     .show(&egui_ctx, |ui| {
-                        ui.heading(datetime.format("%-I:%M:%S %P").to_string());
-
-                        match subtitle {
-                            &Message::Date => {
-                                let suffix = match datetime.day() {
-                                    1 | 21 | 31 => "st",
-                                    2 | 22 => "nd",
-                                    3 | 23 => "rd",
-                                    _ => "th",
+                        panels.day.apply(ui);
+                        ui.heading(clock.time(now));
+
+                        match subtitle.and_then(|i| subtitles.get(i)).map(|x| &x.widget) {
+                            None => {}
+                            Some(Widget::Date) => {
+                                ui.heading(clock.date(now));
+                            }
+                            Some(&Widget::Weather { location }) => {
+                                let i = location.unwrap_or(0);
+                                let msg = match weather_managers.get_mut(i) {
+                                    Some((name, manager)) => {
+                                        let msg = match manager.get() {
+                                            Ok(weather) => format!(
+                                                "{}°C - {}",
+                                                weather.temperature.round(),
+                                                weather.description
+                                            ),
+                                            Err(msg) => msg,
+                                        };
+
+                                        match name {
+                                            Some(name) => format!("{}: {}", name, msg),
+                                            None => msg,
+                                        }
+                                    }
+                                    None => strings.unavailable.to_string(),
+                                };
+
+                                ui.horizontal(|ui| {
+                                    ui.heading(msg);
+
+                                    // Only the first location has history recorded
+                                    if i != 0 {
+                                        return;
+                                    }
+
+                                    let history =
+                                        history.lock().expect("Failed to lock temperature history");
+
+                                    if let Some(trend) = history.trend(chrono::Duration::hours(3)) {
+                                        if trend_arrow(ui, trend, 32.0).clicked() {
+                                            history_opened = Some(Instant::now());
+                                        }
+                                    }
+
+                                    let samples =
+                                        history.since(Utc::now() - chrono::Duration::hours(24));
+                                    if samples.len() > 1
+                                        && sparkline(ui, &samples, Vec2::new(120.0, 32.0)).clicked()
+                                    {
+                                        history_opened = Some(Instant::now());
+                                    }
+                                });
+                            }
+                            Some(Widget::Forecast) => {
+                                let today = weather_managers
+                                    .first_mut()
+                                    .and_then(|(_, manager)| manager.get().ok())
+                                    .and_then(|x| x.daily.first().cloned());
+
+                                let mut parts = Vec::new();
+                                if let Some(today) = today {
+                                    if let Some(high) = today.temp_max {
+                                        parts.push(format!("{} {}°C", strings.high, high.round()));
+                                    }
+                                    if let Some(low) = today.temp_min {
+                                        parts.push(format!("{} {}°C", strings.low, low.round()));
+                                    }
+                                }
+
+                                let msg = if parts.is_empty() {
+                                    strings.unavailable.to_string()
+                                } else {
+                                    parts.join(" - ")
                                 };
 
-                                let msg = format!(
-                                    "{}{} of {}",
-                                    datetime.format("%A, %-d").to_string(),
-                                    suffix,
-                                    datetime.format("%B").to_string()
+                                ui.heading(msg);
+                            }
+                            Some(&Widget::Calendar { within_days }) => {
+                                let today = clock.naive_local(now).date();
+                                let event = upcoming_event(
+                                    schedule
+                                        .lock()
+                                        .expect("Failed to lock sleep schedule")
+                                        .exceptions(),
+                                    today,
+                                    within_days.unwrap_or(CALENDAR_DAYS),
                                 );
 
+                                let msg = match event {
+                                    Some((name, date)) => {
+                                        let days = (date - today).num_days();
+                                        let when = match days {
+                                            0 => strings.today.to_string(),
+                                            1 => strings.tomorrow.to_string(),
+                                            2..=6 => strings.days
+                                                [date.weekday().num_days_from_monday() as usize]
+                                                .to_string(),
+                                            _ => format!(
+                                                "{} {}",
+                                                date.day(),
+                                                strings.months[date.month0() as usize]
+                                            ),
+                                        };
+
+                                        format!("{} - {}", name, when)
+                                    }
+                                    None => strings.unavailable.to_string(),
+                                };
+
                                 ui.heading(msg);
                             }
-                            &Message::Weather => {
-                                let weather = weather_manager.get();
-                                let msg = match weather {
-                                    Ok(weather) => format!(
-                                        "{}°C - {}",
-                                        weather.temperature.round(),
-                                        weather.description
-                                    ),
-                                    Err(msg) => msg,
+                            Some(Widget::Text { text }) => {
+                                ui.heading(text);
+                            }
+                            Some(Widget::Astronomy) => {
+                                let today = weather_managers
+                                    .first_mut()
+                                    .and_then(|(_, manager)| manager.get().ok())
+                                    .and_then(|x| x.daily.first().cloned());
+
+                                let mut parts = Vec::new();
+                                if let Some(today) = today {
+                                    if let Some(sunrise) = today.sunrise {
+                                        parts.push(format!(
+                                            "{} {}",
+                                            strings.sunrise,
+                                            clock.short_time(sunrise)
+                                        ));
+                                    }
+                                    if let Some(sunset) = today.sunset {
+                                        parts.push(format!(
+                                            "{} {}",
+                                            strings.sunset,
+                                            clock.short_time(sunset)
+                                        ));
+                                    }
+                                    if let Some(uv) = today.uv {
+                                        parts.push(format!(
+                                            "{} {}",
+                                            strings.uv,
+                                            locale.uv_category(uv.category)
+                                        ));
+                                    }
+                                }
+
+                                let msg = if parts.is_empty() {
+                                    strings.unavailable.to_string()
+                                } else {
+                                    parts.join(" - ")
                                 };
 
                                 ui.heading(msg);
@@ -346,34 +836,28 @@ pub fn main_loop(config: LeaffrontConfig) {
                     });
             }
             &ScreenState::Night => {
+                history_opened = None;
+
                 egui::Window::new("Night Display")
                     .enabled(true)
                     .resizable(false)
-                    .anchor(Align2::CENTER_CENTER, (night_x, night_y))
+                    .anchor(
+                        panels.night.anchor,
+                        panels.night.offset + Vec2::new(night_x, night_y),
+                    )
                     .auto_sized()
                     .min_width(100.0)
                     .min_height(70.0)
                     .collapsible(false)
                     .title_bar(false)
-                    .frame(Frame::none())
+                    .frame(panels.night.frame(&egui_ctx.style()))
                     .show(&egui_ctx, |ui| {
+                        panels.night.apply(ui);
+
                         // Render out both the top and bottom strings, and center them.
-                        let datetime = Local::now();
-                        let top_msg = datetime.format("%-I:%M:%S %P").to_string();
-
-                        let suffix = match datetime.day() {
-                            1 | 21 | 31 => "st",
-                            2 | 22 => "nd",
-                            3 | 23 => "rd",
-                            _ => "th",
-                        };
-
-                        let bottom_msg = format!(
-                            "{}{} of {}",
-                            datetime.format("%A, %-d").to_string(),
-                            suffix,
-                            datetime.format("%B").to_string()
-                        );
+                        let now = Utc::now();
+                        let top_msg = clock.time(now);
+                        let bottom_msg = clock.date(now);
 
                         ui.vertical_centered(|ui| {
                             ui.heading(top_msg);
@@ -381,7 +865,9 @@ pub fn main_loop(config: LeaffrontConfig) {
                         });
                     });
 
-                if state_countdown.elapsed() > Duration::from_secs(config.night.move_secs) {
+                if panels.night_moves
+                    && state_countdown.elapsed() > Duration::from_secs(config.night.move_secs)
+                {
                     state_countdown = Instant::now();
 
                     // Set new random position
@@ -396,18 +882,82 @@ pub fn main_loop(config: LeaffrontConfig) {
                     night_y = rng.gen_range(min_y..max_y) - screen_height as f32 / 2.0;
                 }
             }
+            &ScreenState::Off => {}
+        }
+
+        // Draw the temperature history page, if it has been opened
+        if let Some(opened) = history_opened {
+            let mut close = opened.elapsed() > Duration::from_secs(HISTORY_PAGE_SECS);
+
+            egui::Window::new("Temperature History")
+                .enabled(true)
+                .resizable(false)
+                .anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+                .auto_sized()
+                .collapsible(false)
+                .title_bar(false)
+                .show(&egui_ctx, |ui| {
+                    ui.heading(strings.history_title);
+
+                    let now = Utc::now();
+                    let start = now - chrono::Duration::hours(24);
+                    let samples = history
+                        .lock()
+                        .expect("Failed to lock temperature history")
+                        .since(start);
+
+                    let size = Vec2::new(screen_width as f32 * 0.6, screen_height as f32 * 0.4);
+                    if history_graph(ui, &samples, start, now, size, &clock).clicked() {
+                        close = true;
+                    }
+                });
+
+            if close {
+                history_opened = None;
+                egui_ctx.request_repaint();
+            }
+        }
+
+        // Draw weather warnings - these stay up until the provider withdraws them
+        for (i, (name, warning)) in warnings.iter().enumerate() {
+            let fill = match warning.severity {
+                WarningSeverity::Major => Color32::from_rgba_unmultiplied(140, 20, 20, 230),
+                WarningSeverity::Minor => Color32::from_rgba_unmultiplied(140, 90, 0, 230),
+            };
+
+            egui::Window::new(format!("Weather Warning {}", i))
+                .enabled(true)
+                .resizable(false)
+                .anchor(panels.notifications.anchor, panels.notifications.stacked(i))
+                .auto_sized()
+                .collapsible(false)
+                .title_bar(false)
+                .frame(panels.notifications.frame(&egui_ctx.style()).fill(fill))
+                .show(&egui_ctx, |ui| {
+                    panels.notifications.apply(ui);
+
+                    ui.heading(match name {
+                        Some(name) => format!("{}: {}", name, warning.title),
+                        None => warning.title.to_owned(),
+                    });
+                });
         }
 
         // Draw notifications
         for (i, notification) in notifications.iter().enumerate() {
+            let i = i + warnings.len();
+
             egui::Window::new(format!("Night Display {}", i))
                 .enabled(true)
                 .resizable(false)
-                .anchor(Align2::RIGHT_TOP, (-10.0, 50.0 + (i as f32 * 120.0)))
+                .anchor(panels.notifications.anchor, panels.notifications.stacked(i))
                 .auto_sized()
                 .collapsible(false)
                 .title_bar(false)
+                .frame(panels.notifications.frame(&egui_ctx.style()))
                 .show(&egui_ctx, |ui| {
+                    panels.notifications.apply(ui);
+
                     ui.heading(notification.source.name.to_owned());
                     ui.heading(notification.source.contents.to_owned());
                 });
@@ -424,7 +974,7 @@ pub fn main_loop(config: LeaffrontConfig) {
                 &ScreenState::Day(..) => {
                     drawer.clear(true);
                 }
-                &ScreenState::Night => {
+                &ScreenState::Night | &ScreenState::Off => {
                     drawer.clear(false);
                 }
             }
@@ -537,9 +1087,29 @@ pub fn main_loop(config: LeaffrontConfig) {
             drawer.end();
         }
 
-        (
-            true,
-            Instant::now() + Duration::from_millis(config.refresh_rate),
-        )
+        // Keep fades smooth
+        let mut next_frame = Duration::from_millis(config.refresh_rate);
+        if dimmer.is_fading() {
+            next_frame = next_frame.min(FADE_INTERVAL);
+        }
+
+        (true, Instant::now() + next_frame)
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_beat_presence() {
+        // Someone in the room holds off the schedule
+        assert!(held_by_presence(None, Some(false)));
+        assert!(!held_by_presence(None, Some(true)));
+        assert!(!held_by_presence(None, None));
+
+        // But not an override
+        assert!(!held_by_presence(Some(DisplayState::Night), Some(false)));
+        assert!(!held_by_presence(Some(DisplayState::Off), Some(false)));
+    }
+}
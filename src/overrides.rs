@@ -0,0 +1,230 @@
+//! Day/Night overrides requested through the REST API, which may expire by themselves.
+
+use crate::schedule::{parse_duration, resolve_local};
+
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Which state the display is in, or has been forced into.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayState {
+    Day,
+    Night,
+    /// The panel is powered down, and nothing is drawn.
+    Off,
+}
+
+/// Forces the display into a state, ignoring the sleep schedule.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Override {
+    pub state: DisplayState,
+    /// When the schedule takes over again. Never, if unset.
+    #[serde(default)]
+    pub expires: Option<DateTime<Utc>>,
+}
+
+/// What the display is doing, as reported by the REST API.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Status {
+    pub state: DisplayState,
+    #[serde(rename = "override")]
+    pub forced: Option<Override>,
+}
+
+/// Works out when an override requested with `for` (e.g. "30m") or `until`
+/// (e.g. "07:00", the next time the clock reads that) should expire. Neither
+/// means the override never expires.
+pub fn parse_expiry<Tz: TimeZone>(
+    duration: Option<&str>,
+    until: Option<&str>,
+    now: &DateTime<Tz>,
+) -> Result<Option<DateTime<Utc>>, String> {
+    match (duration, until) {
+        (None, None) => Ok(None),
+        (Some(_), Some(_)) => Err("Only one of for and until can be set".to_string()),
+        (Some(duration), None) => {
+            let duration = parse_duration(duration.trim()).ok_or_else(|| {
+                format!("Expected a duration like 30m or 1h30m, got {:?}", duration)
+            })?;
+
+            now.with_timezone(&Utc)
+                .checked_add_signed(duration)
+                .map(Some)
+                .ok_or_else(|| format!("Duration {:?} is too long", duration))
+        }
+        (None, Some(until)) => {
+            let time = NaiveTime::parse_from_str(until.trim(), "%H:%M")
+                .map_err(|_| format!("Expected a time as HH:MM, got {:?}", until))?;
+
+            let timezone = now.timezone();
+            let today = now.naive_local().date();
+
+            let mut expiry = resolve_local(&timezone, today.and_time(time));
+            if expiry <= *now {
+                expiry = resolve_local(&timezone, (today + Duration::days(1)).and_time(time));
+            }
+
+            Ok(Some(expiry.with_timezone(&Utc)))
+        }
+    }
+}
+
+/// Holds the current override, persisting it to a file so that it survives restarts.
+pub struct OverrideStore {
+    path: Option<PathBuf>,
+    current: Option<Override>,
+}
+
+impl OverrideStore {
+    /// Creates a store, restoring any override saved in the given file.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let mut current = None;
+
+        if let Some(path) = &path {
+            match fs::read_to_string(path) {
+                Ok(contents) if contents.trim().is_empty() => {}
+                Ok(contents) => match toml::from_str::<Override>(&contents) {
+                    Ok(saved) => {
+                        info!("Restored display override: {:?}", saved);
+                        current = Some(saved);
+                    }
+                    Err(e) => warn!("Ignoring malformed override file {:?}: {:?}", path, e),
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => warn!("Failed to read override file {:?}: {:?}", path, e),
+            }
+        }
+
+        OverrideStore { path, current }
+    }
+
+    /// Replaces the current override. `None` hands control back to the schedule.
+    pub fn set(&mut self, forced: Option<Override>) {
+        self.current = forced;
+
+        if let Err(e) = self.save() {
+            warn!("Failed to persist display override: {:?}", e);
+        }
+    }
+
+    /// Returns the override in effect at the given time, dropping it if it has expired.
+    pub fn active(&mut self, now: DateTime<Utc>) -> Option<&Override> {
+        let expired = self
+            .current
+            .as_ref()
+            .and_then(|x| x.expires)
+            .is_some_and(|expires| expires <= now);
+
+        if expired {
+            info!("Display override expired, returning to schedule");
+            self.set(None);
+        }
+
+        self.current.as_ref()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let contents = match &self.current {
+            Some(forced) => toml::to_string(forced)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            None => String::new(),
+        };
+
+        // Write to a temporary file first so that a crash doesn't leave half an override
+        let mut temp_path = path.clone().into_os_string();
+        temp_path.push(".tmp");
+
+        let mut file = File::create(&temp_path)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+
+        fs::rename(&temp_path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::FixedOffset;
+
+    use tempfile::tempdir;
+
+    fn local(time: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(time).unwrap()
+    }
+
+    #[test]
+    fn parses_expiry() {
+        let now = local("2024-09-14T21:15:00+10:00");
+
+        assert_eq!(parse_expiry(None, None, &now), Ok(None));
+        assert_eq!(
+            parse_expiry(Some("1h30m"), None, &now),
+            Ok(Some(local("2024-09-14T22:45:00+10:00").with_timezone(&Utc)))
+        );
+        // Later today
+        assert_eq!(
+            parse_expiry(None, Some("23:00"), &now),
+            Ok(Some(local("2024-09-14T23:00:00+10:00").with_timezone(&Utc)))
+        );
+        // Tomorrow morning
+        assert_eq!(
+            parse_expiry(None, Some("07:00"), &now),
+            Ok(Some(local("2024-09-15T07:00:00+10:00").with_timezone(&Utc)))
+        );
+
+        assert!(parse_expiry(Some("30"), None, &now).is_err());
+        assert!(parse_expiry(Some("soon"), None, &now).is_err());
+        assert!(parse_expiry(None, Some("7am"), &now).is_err());
+        assert!(parse_expiry(Some("30m"), Some("07:00"), &now).is_err());
+    }
+
+    #[test]
+    fn expires_overrides() {
+        let now = Utc::now();
+        let mut store = OverrideStore::load(None);
+
+        store.set(Some(Override {
+            state: DisplayState::Night,
+            expires: Some(now + Duration::minutes(30)),
+        }));
+
+        assert_eq!(
+            store.active(now).map(|x| x.state),
+            Some(DisplayState::Night)
+        );
+        assert_eq!(store.active(now + Duration::minutes(30)), None);
+        // Stays gone
+        assert_eq!(store.active(now), None);
+    }
+
+    #[test]
+    fn persists_across_loads() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("override.toml");
+        let now = Utc.with_ymd_and_hms(2030, 1, 1, 6, 0, 0).unwrap();
+        let forced = Override {
+            state: DisplayState::Day,
+            expires: Some(now + Duration::hours(1)),
+        };
+
+        OverrideStore::load(Some(path.clone())).set(Some(forced.clone()));
+
+        let mut store = OverrideStore::load(Some(path.clone()));
+        assert_eq!(store.active(now), Some(&forced));
+
+        // Expiring the override clears the file too
+        store.active(now + Duration::hours(2));
+        assert_eq!(OverrideStore::load(Some(path.clone())).active(now), None);
+    }
+}
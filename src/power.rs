@@ -0,0 +1,75 @@
+//! Powers the panel down while the display is Off.
+
+use leaffront_core::brightness::BrightnessControl;
+use leaffront_core::render::Drawer;
+
+use std::io;
+use std::process::Command;
+
+/// How the panel is turned off.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerMethod {
+    /// Through the brightness control, e.g. the backlight's `bl_power`.
+    #[default]
+    Backlight,
+    /// Through DPMS, where the renderer supports it.
+    Dpms,
+    /// By running shell commands, e.g. `vcgencmd display_power 0`.
+    Command,
+}
+
+/// Switches the panel on and off.
+pub struct PowerControl {
+    pub method: PowerMethod,
+    pub on_command: Option<String>,
+    pub off_command: Option<String>,
+}
+
+impl PowerControl {
+    pub fn set<D: Drawer>(
+        &self,
+        on: bool,
+        control: &mut Option<Box<dyn BrightnessControl>>,
+        drawer: &mut D,
+    ) -> io::Result<()> {
+        match self.method {
+            PowerMethod::Backlight => match control {
+                Some(control) => control.set_power(on),
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "No brightness control to switch",
+                )),
+            },
+            PowerMethod::Dpms => drawer.set_display_power(on),
+            PowerMethod::Command => {
+                let command = if on {
+                    &self.on_command
+                } else {
+                    &self.off_command
+                };
+
+                match command {
+                    Some(command) => run(command),
+                    None => Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("No {} command configured", if on { "on" } else { "off" }),
+                    )),
+                }
+            }
+        }
+    }
+}
+
+fn run(command: &str) -> io::Result<()> {
+    let status = Command::new("sh").arg("-c").arg(command).status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{:?} failed: {}",
+            command, status
+        )))
+    }
+}
@@ -0,0 +1,806 @@
+//! Decides when the display should be asleep.
+
+use crate::sun::{sun_times, Coordinates};
+
+use chrono::{
+    DateTime, Datelike, Duration, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+    Weekday,
+};
+
+use std::convert::TryFrom;
+
+/// When a sleep window starts or ends. Written in configuration as "HH:MM", or
+/// relative to the sun as e.g. "sunset+30m" or "sunrise-1h".
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub enum TimeOfDay {
+    /// A wall-clock time.
+    Fixed(NaiveTime),
+    /// An offset from sunrise.
+    Sunrise(Duration),
+    /// An offset from sunset.
+    Sunset(Duration),
+}
+
+impl TimeOfDay {
+    pub fn from_hm(hour: u32, minute: u32) -> Option<Self> {
+        NaiveTime::from_hms_opt(hour, minute, 0).map(TimeOfDay::Fixed)
+    }
+
+    fn is_sun_relative(&self) -> bool {
+        !matches!(self, TimeOfDay::Fixed(_))
+    }
+}
+
+/// Parses a duration such as "30m", "2h" or "1h30m".
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    if value.is_empty() {
+        return None;
+    }
+
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let amount: u32 = digits.parse().ok()?;
+        digits.clear();
+
+        total += match c {
+            'h' => Duration::hours(amount.into()),
+            'm' => Duration::minutes(amount.into()),
+            _ => return None,
+        };
+    }
+
+    // Every amount needs a unit
+    if !digits.is_empty() {
+        return None;
+    }
+
+    Some(total)
+}
+
+/// Parses an offset such as "+30m", "-1h" or "+1h30m". Empty means no offset.
+fn parse_offset(value: &str) -> Option<Duration> {
+    if value.is_empty() {
+        return Some(Duration::zero());
+    }
+
+    if let Some(rest) = value.strip_prefix('+') {
+        parse_duration(rest)
+    } else {
+        value.strip_prefix('-').and_then(parse_duration).map(|x| -x)
+    }
+}
+
+/// Formats an offset as parsed by `parse_offset`.
+fn format_offset(offset: Duration) -> String {
+    let minutes = offset.num_minutes();
+
+    match minutes {
+        0 => String::new(),
+        minutes if minutes % 60 == 0 => format!("{:+}h", minutes / 60),
+        minutes => format!("{:+}m", minutes),
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(value: TimeOfDay) -> Self {
+        match value {
+            TimeOfDay::Fixed(time) => time.format("%H:%M").to_string(),
+            TimeOfDay::Sunrise(offset) => format!("sunrise{}", format_offset(offset)),
+            TimeOfDay::Sunset(offset) => format!("sunset{}", format_offset(offset)),
+        }
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let trimmed = value.trim().to_lowercase();
+        let error = || {
+            format!(
+                "Expected a time as HH:MM, sunrise or sunset (e.g. \"sunset+30m\"), got {:?}",
+                value
+            )
+        };
+
+        if let Some(offset) = trimmed.strip_prefix("sunrise") {
+            return parse_offset(offset)
+                .map(TimeOfDay::Sunrise)
+                .ok_or_else(error);
+        }
+        if let Some(offset) = trimmed.strip_prefix("sunset") {
+            return parse_offset(offset)
+                .map(TimeOfDay::Sunset)
+                .ok_or_else(error);
+        }
+
+        NaiveTime::parse_from_str(&trimmed, "%H:%M")
+            .map(TimeOfDay::Fixed)
+            .map_err(|_| error())
+    }
+}
+
+/// A set of weekdays, written as a list of names in configuration. "weekdays" and
+/// "weekends" may be used as shorthand. Empty means every day.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct Days(Vec<Weekday>);
+
+impl Days {
+    pub fn contains(&self, day: Weekday) -> bool {
+        self.0.is_empty() || self.0.contains(&day)
+    }
+}
+
+impl From<Days> for Vec<String> {
+    fn from(value: Days) -> Self {
+        value
+            .0
+            .iter()
+            .map(|x| x.to_string().to_lowercase())
+            .collect()
+    }
+}
+
+impl TryFrom<Vec<String>> for Days {
+    type Error = String;
+
+    fn try_from(value: Vec<String>) -> Result<Self, Self::Error> {
+        let mut days = Vec::new();
+
+        for name in value {
+            match name.trim().to_lowercase().as_str() {
+                "weekdays" => days.extend(&[
+                    Weekday::Mon,
+                    Weekday::Tue,
+                    Weekday::Wed,
+                    Weekday::Thu,
+                    Weekday::Fri,
+                ]),
+                "weekends" => days.extend(&[Weekday::Sat, Weekday::Sun]),
+                day => days.push(
+                    day.parse()
+                        .map_err(|_| format!("Unknown day of the week: {:?}", name))?,
+                ),
+            }
+        }
+
+        Ok(Days(days))
+    }
+}
+
+/// A period where the display sleeps. If `wake` is at or before `sleep`, the window
+/// runs over midnight into the following day.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SleepWindow {
+    /// Days this window starts on.
+    #[serde(default)]
+    pub days: Days,
+    pub sleep: TimeOfDay,
+    pub wake: TimeOfDay,
+}
+
+/// What an exception does on the dates it covers.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExceptionRule {
+    /// Stay asleep all day, e.g. while away.
+    Asleep,
+    /// Stay awake all day.
+    Awake,
+    /// Turn the display off all day. Counts as asleep as far as the windows go.
+    Off,
+    /// Follow the regular windows for another day of the week, e.g. treating a
+    /// public holiday like a Sunday.
+    Like(Weekday),
+    /// Use these windows instead of the regular ones. Their `days` are ignored.
+    Windows(Vec<SleepWindow>),
+}
+
+/// Overrides the regular schedule for a date, or a range of dates.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScheduleException {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub date: NaiveDate,
+    /// The last date covered, inclusive. Only `date` is covered if unset.
+    #[serde(default)]
+    pub until: Option<NaiveDate>,
+    pub rule: ExceptionRule,
+}
+
+impl ScheduleException {
+    fn covers(&self, date: NaiveDate) -> bool {
+        date >= self.date && date <= self.until.unwrap_or(self.date)
+    }
+
+    /// Returns true if any of the exception's windows are relative to sunrise or
+    /// sunset.
+    pub fn is_sun_relative(&self) -> bool {
+        match &self.rule {
+            ExceptionRule::Windows(windows) => windows
+                .iter()
+                .any(|x| x.sleep.is_sun_relative() || x.wake.is_sun_relative()),
+            _ => false,
+        }
+    }
+}
+
+/// An exception along with the ID it can be removed by.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ExceptionEntry {
+    pub id: u64,
+    #[serde(flatten)]
+    pub exception: ScheduleException,
+}
+
+/// Evaluates a set of sleep windows against the current time.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    windows: Vec<SleepWindow>,
+    /// Where to compute sunrise and sunset for.
+    location: Option<Coordinates>,
+    /// Later exceptions take precedence over earlier ones.
+    exceptions: Vec<ExceptionEntry>,
+    next_exception_id: u64,
+}
+
+impl Schedule {
+    pub fn new(windows: Vec<SleepWindow>, location: Option<Coordinates>) -> Self {
+        Schedule {
+            windows,
+            location,
+            exceptions: Vec::new(),
+            next_exception_id: 1,
+        }
+    }
+
+    /// Returns true if any window is relative to sunrise or sunset.
+    pub fn is_sun_relative(&self) -> bool {
+        self.windows
+            .iter()
+            .any(|x| x.sleep.is_sun_relative() || x.wake.is_sun_relative())
+            || self
+                .exceptions
+                .iter()
+                .any(|x| x.exception.is_sun_relative())
+    }
+
+    /// Returns true if sunrise and sunset can be computed.
+    pub fn has_location(&self) -> bool {
+        self.location.is_some()
+    }
+
+    /// Adds an exception, returning an ID which can be used to remove it.
+    pub fn add_exception(&mut self, exception: ScheduleException) -> u64 {
+        let id = self.next_exception_id;
+        self.next_exception_id += 1;
+
+        self.exceptions.push(ExceptionEntry { id, exception });
+
+        id
+    }
+
+    /// Removes an exception. Returns false if there was no exception with that ID.
+    pub fn remove_exception(&mut self, id: u64) -> bool {
+        let before = self.exceptions.len();
+        self.exceptions.retain(|x| x.id != id);
+        self.exceptions.len() != before
+    }
+
+    pub fn exceptions(&self) -> &[ExceptionEntry] {
+        &self.exceptions
+    }
+
+    fn exception_for(&self, date: NaiveDate) -> Option<&ExceptionRule> {
+        self.exceptions
+            .iter()
+            .rev()
+            .find(|x| x.exception.covers(date))
+            .map(|x| &x.exception.rule)
+    }
+
+    /// Returns the windows which start on the given date.
+    fn windows_for(&self, date: NaiveDate) -> Vec<&SleepWindow> {
+        let day = match self.exception_for(date) {
+            Some(ExceptionRule::Windows(windows)) => return windows.iter().collect(),
+            Some(ExceptionRule::Like(day)) => *day,
+            // Whole-day exceptions are handled by `is_night`. Their regular windows
+            // still carry over into the next day, so that e.g. coming back from
+            // being away doesn't wake the display at midnight.
+            _ => date.weekday(),
+        };
+
+        self.windows
+            .iter()
+            .filter(|x| x.days.contains(day))
+            .collect()
+    }
+
+    /// Works out when a time falls on the given local date. Returns `None` for
+    /// sun-relative times if the sun doesn't rise or set that day, or no location
+    /// was configured.
+    fn resolve<Tz: TimeZone>(
+        &self,
+        timezone: &Tz,
+        date: NaiveDate,
+        time: TimeOfDay,
+    ) -> Option<DateTime<Tz>> {
+        let sun = || sun_times(date, self.location?);
+
+        Some(match time {
+            TimeOfDay::Fixed(time) => resolve_local(timezone, date.and_time(time)),
+            TimeOfDay::Sunrise(offset) => sun()?.0.with_timezone(timezone) + offset,
+            TimeOfDay::Sunset(offset) => sun()?.1.with_timezone(timezone) + offset,
+        })
+    }
+
+    /// Returns true if the given time falls within any sleep window, taking
+    /// exceptions for the date into account.
+    pub fn is_night<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> bool {
+        let timezone = now.timezone();
+        let today = now.naive_local().date();
+
+        match self.exception_for(today) {
+            Some(ExceptionRule::Asleep) | Some(ExceptionRule::Off) => return true,
+            Some(ExceptionRule::Awake) => return false,
+            _ => {}
+        }
+
+        // A window that started yesterday may still be running
+        for date in &[today - Duration::days(1), today] {
+            for window in self.windows_for(*date) {
+                let start = match self.resolve(&timezone, *date, window.sleep) {
+                    Some(start) => start,
+                    None => continue,
+                };
+
+                let mut end = self.resolve(&timezone, *date, window.wake);
+                if end.as_ref().is_none_or(|end| *end <= start) {
+                    end = self.resolve(&timezone, *date + Duration::days(1), window.wake);
+                }
+
+                if end.is_some_and(|end| start <= *now && *now < end) {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Returns true if an exception turns the display off for the given time's date.
+    pub fn is_off<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> bool {
+        self.exception_for(now.naive_local().date()) == Some(&ExceptionRule::Off)
+    }
+
+    /// Returns how long until `is_night` next changes, looking no further ahead than
+    /// `limit`. Accurate to the second.
+    pub fn until_change<Tz: TimeZone>(
+        &self,
+        now: &DateTime<Tz>,
+        limit: Duration,
+    ) -> Option<Duration> {
+        let current = self.is_night(now);
+        let changed = |offset: Duration| self.is_night(&(now.clone() + offset)) != current;
+
+        // Windows are set to the minute, so step through those first
+        let mut offset = Duration::zero();
+        while offset < limit {
+            let next = std::cmp::min(offset + Duration::minutes(1), limit);
+
+            if changed(next) {
+                // Narrow it down to the second
+                let (mut before, mut after) = (offset, next);
+                while after - before > Duration::seconds(1) {
+                    let middle = before + Duration::seconds((after - before).num_seconds() / 2);
+                    if changed(middle) {
+                        after = middle;
+                    } else {
+                        before = middle;
+                    }
+                }
+
+                return Some(after);
+            }
+
+            offset = next;
+        }
+
+        None
+    }
+}
+
+/// Converts a wall-clock time to an instant. Times repeated when clocks go back
+/// resolve to their first occurrence, and times skipped when clocks go forward
+/// resolve to the end of the gap.
+pub fn resolve_local<Tz: TimeZone>(timezone: &Tz, time: NaiveDateTime) -> DateTime<Tz> {
+    let mut candidate = time;
+
+    loop {
+        match timezone.from_local_datetime(&candidate) {
+            LocalResult::Single(time) => return time,
+            LocalResult::Ambiguous(earliest, _) => return earliest,
+            LocalResult::None => candidate += Duration::minutes(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::{FixedOffset, NaiveDate};
+    use chrono_tz::Australia::Sydney;
+
+    fn window(days: &[&str], sleep: &str, wake: &str) -> SleepWindow {
+        SleepWindow {
+            days: Days::try_from(days.iter().map(|x| x.to_string()).collect::<Vec<_>>()).unwrap(),
+            sleep: TimeOfDay::try_from(sleep.to_string()).unwrap(),
+            wake: TimeOfDay::try_from(wake.to_string()).unwrap(),
+        }
+    }
+
+    fn new_schedule(windows: Vec<SleepWindow>) -> Schedule {
+        Schedule::new(windows, None)
+    }
+
+    /// A local time at a fixed offset, so results don't depend on the machine.
+    fn at(date: (i32, u32, u32), time: (u32, u32)) -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(10 * 3600)
+            .unwrap()
+            .from_local_datetime(
+                &NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+                    .unwrap()
+                    .and_hms_opt(time.0, time.1, 0)
+                    .unwrap(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_config_values() {
+        assert_eq!(
+            TimeOfDay::try_from("7:05".to_string()),
+            Ok(TimeOfDay::from_hm(7, 5).unwrap())
+        );
+        assert!(TimeOfDay::try_from("24:00".to_string()).is_err());
+        assert!(TimeOfDay::try_from("22".to_string()).is_err());
+
+        let days = Days::try_from(vec!["Weekends".to_string(), "mon".to_string()]).unwrap();
+        assert!(days.contains(Weekday::Sat));
+        assert!(days.contains(Weekday::Mon));
+        assert!(!days.contains(Weekday::Tue));
+        assert!(Days::default().contains(Weekday::Wed));
+        assert!(Days::try_from(vec!["someday".to_string()]).is_err());
+
+        assert_eq!(
+            TimeOfDay::try_from("Sunset+30m".to_string()),
+            Ok(TimeOfDay::Sunset(Duration::minutes(30)))
+        );
+        assert_eq!(
+            TimeOfDay::try_from("sunrise-1h15m".to_string()),
+            Ok(TimeOfDay::Sunrise(Duration::minutes(-75)))
+        );
+        assert_eq!(
+            TimeOfDay::try_from("sunrise".to_string()),
+            Ok(TimeOfDay::Sunrise(Duration::zero()))
+        );
+        assert!(TimeOfDay::try_from("sunset+30".to_string()).is_err());
+        assert!(TimeOfDay::try_from("sunset 30m".to_string()).is_err());
+    }
+
+    #[test]
+    fn finds_next_change() {
+        let schedule = new_schedule(vec![window(&[], "22:00", "07:30")]);
+        let limit = Duration::hours(1);
+
+        assert_eq!(
+            schedule.until_change(&at((2024, 9, 2), (21, 15)), limit),
+            Some(Duration::minutes(45))
+        );
+        assert_eq!(
+            schedule.until_change(&(at((2024, 9, 3), (7, 0)) + Duration::seconds(20)), limit),
+            Some(Duration::seconds(29 * 60 + 40))
+        );
+        assert_eq!(
+            schedule.until_change(&at((2024, 9, 2), (20, 59)), limit),
+            None
+        );
+        assert_eq!(
+            new_schedule(Vec::new()).until_change(&at((2024, 9, 2), (12, 0)), limit),
+            None
+        );
+    }
+
+    #[test]
+    fn window_crosses_midnight() {
+        let schedule = new_schedule(vec![window(&[], "22:00", "07:30")]);
+
+        assert!(!schedule.is_night(&at((2024, 9, 2), (21, 59))));
+        assert!(schedule.is_night(&at((2024, 9, 2), (22, 0))));
+        assert!(schedule.is_night(&at((2024, 9, 3), (0, 0))));
+        assert!(schedule.is_night(&at((2024, 9, 3), (7, 29))));
+        assert!(!schedule.is_night(&at((2024, 9, 3), (7, 30))));
+        assert!(!schedule.is_night(&at((2024, 9, 3), (12, 0))));
+    }
+
+    #[test]
+    fn multiple_windows_per_day() {
+        let schedule = new_schedule(vec![
+            window(&[], "13:00", "14:15"),
+            window(&[], "23:00", "06:00"),
+        ]);
+
+        assert!(!schedule.is_night(&at((2024, 9, 2), (12, 59))));
+        assert!(schedule.is_night(&at((2024, 9, 2), (13, 30))));
+        assert!(!schedule.is_night(&at((2024, 9, 2), (14, 15))));
+        assert!(schedule.is_night(&at((2024, 9, 2), (23, 30))));
+        assert!(schedule.is_night(&at((2024, 9, 3), (5, 0))));
+    }
+
+    #[test]
+    fn weekday_windows() {
+        // Sleep in on weekends - 2024-09-06 is a Friday
+        let schedule = new_schedule(vec![
+            window(&["sun", "mon", "tue", "wed", "thu"], "22:00", "07:00"),
+            window(&["fri", "sat"], "23:30", "09:00"),
+        ]);
+
+        assert!(schedule.is_night(&at((2024, 9, 5), (22, 30))));
+        assert!(!schedule.is_night(&at((2024, 9, 6), (7, 30))));
+        assert!(!schedule.is_night(&at((2024, 9, 6), (22, 30))));
+        assert!(schedule.is_night(&at((2024, 9, 6), (23, 45))));
+        assert!(schedule.is_night(&at((2024, 9, 7), (8, 30))));
+        assert!(schedule.is_night(&at((2024, 9, 8), (8, 30))));
+        assert!(schedule.is_night(&at((2024, 9, 8), (22, 30))));
+        assert!(!schedule.is_night(&at((2024, 9, 9), (8, 30))));
+    }
+
+    #[test]
+    fn handles_clocks_going_forward() {
+        // Sydney skips from 2:00 to 3:00 on 2024-10-06
+        let schedule = new_schedule(vec![
+            window(&["sat"], "22:00", "02:30"),
+            window(&["sun"], "02:15", "04:00"),
+        ]);
+        let local = |hour, minute| {
+            Sydney
+                .with_ymd_and_hms(2024, 10, 6, hour, minute, 0)
+                .single()
+                .unwrap()
+        };
+
+        assert!(schedule.is_night(&local(1, 59)));
+        // Both the wake and following sleep times were skipped, and resolve to 3:00
+        assert!(schedule.is_night(&local(3, 0)));
+        assert!(schedule.is_night(&local(3, 59)));
+        assert!(!schedule.is_night(&local(4, 0)));
+
+        let schedule = new_schedule(vec![window(&["sat"], "22:00", "02:30")]);
+        assert!(schedule.is_night(&local(1, 59)));
+        assert!(!schedule.is_night(&local(3, 0)));
+    }
+
+    #[test]
+    fn handles_clocks_going_back() {
+        // Sydney repeats 2:00 to 3:00 on 2024-04-07
+        let schedule = new_schedule(vec![window(&["sat"], "22:00", "02:30")]);
+        let utc = |hour, minute| {
+            chrono::Utc
+                .with_ymd_and_hms(2024, 4, 6, hour, minute, 0)
+                .single()
+                .unwrap()
+                .with_timezone(&Sydney)
+        };
+
+        // 2:15 AEDT, the first time around
+        assert!(schedule.is_night(&utc(15, 15)));
+        // 2:30 AEDT
+        assert!(!schedule.is_night(&utc(15, 30)));
+        // 2:15 AEST, the second time around, should stay awake
+        assert!(!schedule.is_night(&utc(16, 15)));
+
+        // An overnight window still ends at the right wall-clock time
+        let schedule = new_schedule(vec![window(&[], "22:00", "07:00")]);
+        assert!(schedule.is_night(&utc(16, 45)));
+        // 6:59 and 7:00 AEST
+        assert!(schedule.is_night(&utc(20, 59)));
+        assert!(!schedule.is_night(&utc(21, 0)));
+    }
+
+    #[test]
+    fn follows_the_sun() {
+        let sydney = Coordinates {
+            latitude: -33.8679,
+            longitude: 151.2073,
+        };
+        let schedule = Schedule::new(vec![window(&[], "sunset+30m", "sunrise")], Some(sydney));
+        let local = |date: (i32, u32, u32), time: (u32, u32)| {
+            Sydney
+                .with_ymd_and_hms(date.0, date.1, date.2, time.0, time.1, 0)
+                .single()
+                .unwrap()
+        };
+
+        // Summer - sunset around 8:05pm, sunrise around 5:41am
+        assert!(!schedule.is_night(&local((2024, 12, 21), (20, 25))));
+        assert!(schedule.is_night(&local((2024, 12, 21), (20, 45))));
+        assert!(schedule.is_night(&local((2024, 12, 22), (5, 30))));
+        assert!(!schedule.is_night(&local((2024, 12, 22), (5, 50))));
+
+        // Winter - sunset around 4:54pm, sunrise around 7:00am
+        assert!(schedule.is_night(&local((2024, 6, 21), (17, 30))));
+        assert!(schedule.is_night(&local((2024, 6, 22), (6, 50))));
+        assert!(!schedule.is_night(&local((2024, 6, 22), (7, 10))));
+
+        // Without a location, sun-relative windows never apply
+        let schedule = new_schedule(vec![window(&[], "sunset", "sunrise")]);
+        assert!(!schedule.is_night(&local((2024, 6, 21), (23, 0))));
+    }
+
+    fn exception(
+        date: (i32, u32, u32),
+        until: Option<(i32, u32, u32)>,
+        rule: ExceptionRule,
+    ) -> ScheduleException {
+        let to_date = |x: (i32, u32, u32)| NaiveDate::from_ymd_opt(x.0, x.1, x.2).unwrap();
+
+        ScheduleException {
+            name: None,
+            date: to_date(date),
+            until: until.map(to_date),
+            rule,
+        }
+    }
+
+    #[test]
+    fn finds_sun_relative_exceptions() {
+        let mut schedule = new_schedule(vec![window(&[], "22:00", "07:00")]);
+        assert!(!schedule.is_sun_relative());
+        assert!(!schedule.has_location());
+
+        let fixed = exception((2024, 12, 25), None, ExceptionRule::Asleep);
+        let sunset = exception(
+            (2024, 12, 26),
+            None,
+            ExceptionRule::Windows(vec![window(&[], "sunset", "07:00")]),
+        );
+        assert!(!fixed.is_sun_relative());
+        assert!(sunset.is_sun_relative());
+
+        schedule.add_exception(sunset);
+        assert!(schedule.is_sun_relative());
+    }
+
+    #[test]
+    fn exceptions_override_windows() {
+        // 2024-12-25 is a Wednesday
+        let mut schedule = new_schedule(vec![
+            window(&["weekdays"], "09:00", "17:00"),
+            window(&["sun", "mon", "tue", "wed", "thu"], "22:00", "07:00"),
+            window(&["fri", "sat"], "23:30", "09:00"),
+        ]);
+        assert!(schedule.is_night(&at((2024, 12, 25), (12, 0))));
+
+        // Treat Christmas like a Saturday
+        schedule.add_exception(exception(
+            (2024, 12, 25),
+            None,
+            ExceptionRule::Like(Weekday::Sat),
+        ));
+        assert!(!schedule.is_night(&at((2024, 12, 25), (12, 0))));
+        assert!(!schedule.is_night(&at((2024, 12, 25), (23, 0))));
+        assert!(schedule.is_night(&at((2024, 12, 26), (8, 30))));
+
+        // Stay up late on New Year's Eve, then sleep in
+        schedule.add_exception(exception(
+            (2024, 12, 31),
+            None,
+            ExceptionRule::Windows(Vec::new()),
+        ));
+        schedule.add_exception(exception(
+            (2025, 1, 1),
+            None,
+            ExceptionRule::Windows(vec![window(&[], "01:00", "10:00")]),
+        ));
+        assert!(schedule.is_night(&at((2024, 12, 31), (0, 30))));
+        assert!(!schedule.is_night(&at((2024, 12, 31), (23, 0))));
+        assert!(!schedule.is_night(&at((2025, 1, 1), (0, 30))));
+        assert!(schedule.is_night(&at((2025, 1, 1), (9, 30))));
+        assert!(!schedule.is_night(&at((2025, 1, 1), (12, 0))));
+
+        // Stay awake all day - even through yesterday's overnight window
+        schedule.add_exception(exception((2024, 12, 24), None, ExceptionRule::Awake));
+        assert!(!schedule.is_night(&at((2024, 12, 24), (3, 0))));
+        assert!(!schedule.is_night(&at((2024, 12, 24), (12, 0))));
+    }
+
+    #[test]
+    fn away_ranges() {
+        let mut schedule = new_schedule(vec![window(&[], "22:00", "07:00")]);
+        let id = schedule.add_exception(exception(
+            (2025, 1, 4),
+            Some((2025, 1, 18)),
+            ExceptionRule::Asleep,
+        ));
+
+        assert!(!schedule.is_night(&at((2025, 1, 3), (12, 0))));
+        assert!(schedule.is_night(&at((2025, 1, 4), (12, 0))));
+        assert!(schedule.is_night(&at((2025, 1, 18), (12, 0))));
+        // The regular overnight window carries over after coming home
+        assert!(schedule.is_night(&at((2025, 1, 19), (1, 0))));
+        assert!(!schedule.is_night(&at((2025, 1, 19), (12, 0))));
+
+        // The most recent exception wins
+        schedule.add_exception(exception((2025, 1, 10), None, ExceptionRule::Awake));
+        assert!(!schedule.is_night(&at((2025, 1, 10), (12, 0))));
+
+        assert!(schedule.remove_exception(id));
+        assert!(!schedule.remove_exception(id));
+        assert!(!schedule.is_night(&at((2025, 1, 4), (12, 0))));
+        assert_eq!(schedule.exceptions().len(), 1);
+
+        // Turning the display off while away still counts as asleep
+        schedule.add_exception(exception(
+            (2025, 2, 1),
+            Some((2025, 2, 2)),
+            ExceptionRule::Off,
+        ));
+        assert!(schedule.is_off(&at((2025, 2, 1), (12, 0))));
+        assert!(schedule.is_night(&at((2025, 2, 2), (12, 0))));
+        assert!(!schedule.is_off(&at((2025, 2, 3), (1, 0))));
+        assert!(schedule.is_night(&at((2025, 2, 3), (1, 0))));
+    }
+
+    #[test]
+    fn exceptions_round_trip() {
+        let value = r#"
+            [[exceptions]]
+            name = "Boxing Day"
+            date = "2024-12-26"
+            rule = { like = "sun" }
+
+            [[exceptions]]
+            date = "2025-01-04"
+            until = "2025-01-18"
+            rule = "asleep"
+
+            [[exceptions]]
+            date = "2024-12-31"
+            rule = { windows = [{ sleep = "sunset+1h30m", wake = "10:00" }] }
+        "#;
+
+        #[derive(Deserialize)]
+        struct Exceptions {
+            exceptions: Vec<ScheduleException>,
+        }
+
+        let exceptions: Exceptions = toml::from_str(value).unwrap();
+        let exceptions = exceptions.exceptions;
+
+        assert_eq!(exceptions[0].rule, ExceptionRule::Like(Weekday::Sun));
+        assert_eq!(exceptions[1].rule, ExceptionRule::Asleep);
+        assert_eq!(
+            exceptions[2].rule,
+            ExceptionRule::Windows(vec![SleepWindow {
+                days: Days::default(),
+                sleep: TimeOfDay::Sunset(Duration::minutes(90)),
+                wake: TimeOfDay::from_hm(10, 0).unwrap(),
+            }])
+        );
+
+        for exception in exceptions {
+            let value = toml::Value::try_from(&exception).unwrap();
+            assert_eq!(value.try_into::<ScheduleException>().unwrap(), exception);
+        }
+    }
+}
@@ -1,25 +1,24 @@
 /// Represents different states that the display can be in
 use leaffront_core::backend::Notification;
 
+use crate::overrides::DisplayState;
+
 use std::time::Instant;
 
 #[derive(PartialEq, Eq)]
 pub enum ScreenState {
-    Day(Message),
+    /// Shows the subtitle at this index, if there is one to show.
+    Day(Option<usize>),
     Night,
+    Off,
 }
 
-#[derive(PartialEq, Eq)]
-pub enum Message {
-    Date,
-    Weather,
-}
-
-impl Message {
-    pub fn next(&self) -> Self {
+impl ScreenState {
+    pub fn display_state(&self) -> DisplayState {
         match self {
-            &Message::Date => Message::Weather,
-            &Message::Weather => Message::Date,
+            ScreenState::Day(_) => DisplayState::Day,
+            ScreenState::Night => DisplayState::Night,
+            ScreenState::Off => DisplayState::Off,
         }
     }
 }
@@ -0,0 +1,328 @@
+//! The rotating line of information under the clock on the Day display.
+
+use crate::schedule::{Days, ExceptionEntry};
+
+use chrono::{Datelike, Duration as DateDuration, NaiveDate, NaiveDateTime, NaiveTime};
+
+use std::time::Duration;
+
+/// How many days ahead the calendar looks, unless configured otherwise.
+pub static CALENDAR_DAYS: u32 = 7;
+
+/// What a subtitle shows.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "widget", rename_all = "lowercase")]
+pub enum Widget {
+    Date,
+    /// Current conditions. Without a location (counted from 0), shows each
+    /// configured location in turn.
+    Weather {
+        #[serde(default)]
+        location: Option<usize>,
+    },
+    /// Today's high and low for the first location.
+    Forecast,
+    /// Sunrise, sunset and UV for the first location.
+    Astronomy,
+    /// The next named exception in the sleep schedule, e.g. a holiday, if there is
+    /// one within `within_days`.
+    Calendar {
+        #[serde(default)]
+        within_days: Option<u32>,
+    },
+    Text {
+        text: String,
+    },
+}
+
+/// A subtitle as written in configuration.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Subtitle {
+    #[serde(flatten)]
+    pub widget: Widget,
+    /// Seconds to show it for. Defaults to `subtitle_secs`.
+    #[serde(default)]
+    pub secs: Option<u64>,
+    /// Days to show it on. Every day, if empty.
+    #[serde(default)]
+    pub days: Days,
+    /// Times of day (HH:MM) to show it between. May run past midnight.
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub until: Option<String>,
+}
+
+/// A subtitle ready to be shown.
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub widget: Widget,
+    pub duration: Duration,
+    days: Days,
+    from: Option<NaiveTime>,
+    until: Option<NaiveTime>,
+}
+
+impl Page {
+    fn new(widget: Widget, duration: Duration) -> Self {
+        Page {
+            widget,
+            duration,
+            days: Days::default(),
+            from: None,
+            until: None,
+        }
+    }
+
+    /// Returns true if the page should be shown at this time of the week.
+    pub fn is_enabled(&self, now: NaiveDateTime) -> bool {
+        let time = now.time();
+
+        let in_hours = match (self.from, self.until) {
+            (Some(from), Some(until)) if from <= until => from <= time && time < until,
+            (Some(from), Some(until)) => from <= time || time < until,
+            (Some(from), None) => from <= time,
+            (None, Some(until)) => time < until,
+            (None, None) => true,
+        };
+
+        in_hours && self.days.contains(now.weekday())
+    }
+}
+
+/// The pages to rotate through, in order.
+#[derive(Clone, Debug)]
+pub struct Subtitles {
+    pages: Vec<Page>,
+}
+
+impl Subtitles {
+    /// Without any subtitles configured, shows the date, the weather for each
+    /// location, then sunrise and sunset.
+    pub fn new(
+        subtitles: &[Subtitle],
+        default_secs: u64,
+        weather_locations: usize,
+    ) -> Result<Self, String> {
+        let default_duration = Duration::from_secs(default_secs);
+
+        if subtitles.is_empty() {
+            let mut pages = vec![Page::new(Widget::Date, default_duration)];
+            pages.extend(
+                (0..weather_locations)
+                    .map(|i| Page::new(Widget::Weather { location: Some(i) }, default_duration)),
+            );
+            pages.push(Page::new(Widget::Astronomy, default_duration));
+
+            return Ok(Subtitles { pages });
+        }
+
+        let parse_time = |time: &Option<String>| {
+            time.as_ref()
+                .map(|x| {
+                    NaiveTime::parse_from_str(x.trim(), "%H:%M")
+                        .map_err(|_| format!("Expected a subtitle time as HH:MM, got {:?}", x))
+                })
+                .transpose()
+        };
+
+        let mut pages = Vec::new();
+
+        for subtitle in subtitles {
+            let template = Page {
+                widget: subtitle.widget.clone(),
+                duration: subtitle.secs.map_or(default_duration, Duration::from_secs),
+                days: subtitle.days.clone(),
+                from: parse_time(&subtitle.from)?,
+                until: parse_time(&subtitle.until)?,
+            };
+
+            match subtitle.widget {
+                Widget::Weather { location: None } => {
+                    pages.extend((0..weather_locations).map(|i| Page {
+                        widget: Widget::Weather { location: Some(i) },
+                        ..template.clone()
+                    }));
+                }
+                Widget::Weather {
+                    location: Some(location),
+                } if location >= weather_locations => {
+                    return Err(format!(
+                        "Weather subtitle shows location {}, but only {} are configured",
+                        location, weather_locations
+                    ));
+                }
+                _ => pages.push(template),
+            }
+        }
+
+        Ok(Subtitles { pages })
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Page> {
+        self.pages.get(index)
+    }
+
+    /// Returns the page to show after `current` (or first, if `None`), skipping
+    /// any that are disabled right now or have nothing to show. Stays on the
+    /// current page if it's the only one left. `None` if there is nothing to show.
+    pub fn next<F: Fn(&Widget) -> bool>(
+        &self,
+        current: Option<usize>,
+        now: NaiveDateTime,
+        available: F,
+    ) -> Option<usize> {
+        let len = self.pages.len();
+        let start = current.map_or(0, |x| x + 1);
+
+        (0..len)
+            .map(|offset| (start + offset) % len)
+            .find(|&i| self.pages[i].is_enabled(now) && available(&self.pages[i].widget))
+    }
+}
+
+/// Finds the first named exception which covers a day from `today` to
+/// `within_days` later, returning its name and the first such day.
+pub fn upcoming_event(
+    exceptions: &[ExceptionEntry],
+    today: NaiveDate,
+    within_days: u32,
+) -> Option<(String, NaiveDate)> {
+    let last = today + DateDuration::days(within_days as i64);
+
+    exceptions
+        .iter()
+        .map(|x| &x.exception)
+        .filter(|x| x.until.unwrap_or(x.date) >= today && x.date <= last)
+        .filter_map(|x| x.name.clone().map(|name| (name, x.date.max(today))))
+        .min_by_key(|(_, date)| *date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::schedule::{ExceptionRule, ScheduleException};
+
+    fn at(date: (i32, u32, u32), time: (u32, u32)) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+            .unwrap()
+            .and_hms_opt(time.0, time.1, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn defaults_to_date_weather_and_astronomy() {
+        let subtitles = Subtitles::new(&[], 8, 2).unwrap();
+        let now = at((2025, 1, 6), (12, 0));
+        let widget = |i: Option<usize>| i.map(|i| subtitles.get(i).unwrap().widget.clone());
+
+        let first = subtitles.next(None, now, |_| true);
+        assert_eq!(widget(first), Some(Widget::Date));
+        let second = subtitles.next(first, now, |_| true);
+        assert_eq!(widget(second), Some(Widget::Weather { location: Some(0) }));
+
+        // Astronomy is skipped without data
+        let no_astronomy = |x: &Widget| *x != Widget::Astronomy;
+        let fourth = subtitles.next(Some(2), now, no_astronomy);
+        assert_eq!(widget(fourth), Some(Widget::Date));
+
+        assert_eq!(subtitles.get(0).unwrap().duration, Duration::from_secs(8));
+        assert_eq!(subtitles.next(None, now, |_| false), None);
+    }
+
+    #[test]
+    fn follows_conditions() {
+        let config: toml::Value = toml::from_str(
+            r#"
+            [[subtitles]]
+            widget = "date"
+            secs = 4
+
+            [[subtitles]]
+            widget = "text"
+            text = "Bins go out tonight"
+            days = ["tue"]
+            from = "17:00"
+            until = "02:00"
+
+            [[subtitles]]
+            widget = "weather"
+            "#,
+        )
+        .unwrap();
+        let subtitles: Vec<Subtitle> = config["subtitles"].clone().try_into().unwrap();
+        let subtitles = Subtitles::new(&subtitles, 8, 2).unwrap();
+
+        // 2025-01-07 is a Tuesday
+        assert_eq!(
+            subtitles.next(Some(0), at((2025, 1, 7), (18, 0)), |_| true),
+            Some(1)
+        );
+        assert_eq!(
+            subtitles.next(Some(0), at((2025, 1, 7), (12, 0)), |_| true),
+            Some(2)
+        );
+        assert_eq!(
+            subtitles.next(Some(0), at((2025, 1, 8), (18, 0)), |_| true),
+            Some(2)
+        );
+        assert_eq!(
+            subtitles.next(Some(3), at((2025, 1, 7), (18, 0)), |_| true),
+            Some(0)
+        );
+
+        assert_eq!(subtitles.get(0).unwrap().duration, Duration::from_secs(4));
+        assert_eq!(
+            subtitles.get(3).unwrap().widget,
+            Widget::Weather { location: Some(1) }
+        );
+
+        // Only the current page is left
+        let only_date = |x: &Widget| *x == Widget::Date;
+        assert_eq!(
+            subtitles.next(Some(0), at((2025, 1, 7), (12, 0)), only_date),
+            Some(0)
+        );
+
+        let bad = vec![Subtitle {
+            widget: Widget::Weather { location: Some(2) },
+            secs: None,
+            days: Days::default(),
+            from: None,
+            until: None,
+        }];
+        assert!(Subtitles::new(&bad, 8, 2).is_err());
+    }
+
+    #[test]
+    fn finds_upcoming_events() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 12, day).unwrap();
+        let entry = |id, name: Option<&str>, from, until: Option<u32>| ExceptionEntry {
+            id,
+            exception: ScheduleException {
+                name: name.map(|x| x.to_string()),
+                date: date(from),
+                until: until.map(date),
+                rule: ExceptionRule::Asleep,
+            },
+        };
+
+        let exceptions = vec![
+            entry(1, Some("Christmas"), 25, None),
+            entry(2, None, 20, None),
+            entry(3, Some("Away"), 18, Some(22)),
+        ];
+
+        assert_eq!(
+            upcoming_event(&exceptions, date(20), 7),
+            Some(("Away".to_string(), date(20)))
+        );
+        assert_eq!(
+            upcoming_event(&exceptions, date(23), 7),
+            Some(("Christmas".to_string(), date(25)))
+        );
+        assert_eq!(upcoming_event(&exceptions, date(23), 1), None);
+        assert_eq!(upcoming_event(&exceptions, date(26), 7), None);
+    }
+}
@@ -0,0 +1,132 @@
+//! Computes sunrise and sunset times offline, using the NOAA sunrise equation.
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+/// A point on the Earth, in decimal degrees. North and east are positive.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Julian date of the J2000 epoch (2000-01-01 12:00 UTC).
+static J2000: f64 = 2451545.0;
+
+/// Julian date of the Unix epoch.
+static UNIX_EPOCH_JULIAN: f64 = 2440587.5;
+
+/// Angle of the sun below the horizon at sunrise and sunset, accounting for
+/// refraction and the size of the sun's disc.
+static HORIZON_DEGREES: f64 = -0.833;
+
+/// Axial tilt of the Earth.
+static OBLIQUITY_DEGREES: f64 = 23.4397;
+
+/// Returns the sunrise and sunset for the given local date, or `None` if the sun
+/// doesn't rise or set that day (near the poles).
+pub fn sun_times(date: NaiveDate, location: Coordinates) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let epoch = NaiveDate::from_ymd_opt(2000, 1, 1).expect("J2000 should be valid");
+    let days = (date - epoch).num_days() as f64;
+
+    // Mean solar noon, as days since J2000
+    let noon = days - location.longitude / 360.0;
+
+    let anomaly = (357.5291 + 0.98560028 * noon)
+        .rem_euclid(360.0)
+        .to_radians();
+    let center =
+        1.9148 * anomaly.sin() + 0.0200 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+    let ecliptic_longitude = (anomaly.to_degrees() + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+
+    let transit = J2000 + noon + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic_longitude).sin();
+
+    let declination = (ecliptic_longitude.sin() * OBLIQUITY_DEGREES.to_radians().sin()).asin();
+    let latitude = location.latitude.to_radians();
+
+    let cos_hour_angle = (HORIZON_DEGREES.to_radians().sin() - latitude.sin() * declination.sin())
+        / (latitude.cos() * declination.cos());
+
+    // The sun stays above or below the horizon all day
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+
+    let hour_angle = cos_hour_angle.acos().to_degrees() / 360.0;
+
+    Some((
+        from_julian(transit - hour_angle)?,
+        from_julian(transit + hour_angle)?,
+    ))
+}
+
+fn from_julian(julian: f64) -> Option<DateTime<Utc>> {
+    let secs = ((julian - UNIX_EPOCH_JULIAN) * 86400.0).round() as i64;
+    Utc.timestamp_opt(secs, 0).single()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: DateTime<Utc>, expected: &str) {
+        let expected = DateTime::parse_from_rfc3339(expected).unwrap();
+        let difference = (actual.timestamp() - expected.timestamp()).abs();
+        assert!(
+            difference <= 120,
+            "Expected {} to be within two minutes of {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn computes_sydney() {
+        let sydney = Coordinates {
+            latitude: -33.8679,
+            longitude: 151.2073,
+        };
+
+        let (sunrise, sunset) =
+            sun_times(NaiveDate::from_ymd_opt(2024, 9, 14).unwrap(), sydney).unwrap();
+        assert_close(sunrise, "2024-09-14T05:56:00+10:00");
+        assert_close(sunset, "2024-09-14T17:46:00+10:00");
+
+        let (sunrise, sunset) =
+            sun_times(NaiveDate::from_ymd_opt(2024, 12, 21).unwrap(), sydney).unwrap();
+        assert_close(sunrise, "2024-12-21T05:41:00+11:00");
+        assert_close(sunset, "2024-12-21T20:05:00+11:00");
+    }
+
+    #[test]
+    fn computes_western_hemisphere() {
+        let new_york = Coordinates {
+            latitude: 40.7128,
+            longitude: -74.0060,
+        };
+
+        let (sunrise, sunset) =
+            sun_times(NaiveDate::from_ymd_opt(2024, 6, 20).unwrap(), new_york).unwrap();
+        assert_close(sunrise, "2024-06-20T05:25:00-04:00");
+        assert_close(sunset, "2024-06-20T20:31:00-04:00");
+    }
+
+    #[test]
+    fn handles_polar_night() {
+        let tromso = Coordinates {
+            latitude: 69.6492,
+            longitude: 18.9553,
+        };
+
+        assert_eq!(
+            sun_times(NaiveDate::from_ymd_opt(2024, 12, 21).unwrap(), tromso),
+            None
+        );
+        assert_eq!(
+            sun_times(NaiveDate::from_ymd_opt(2024, 6, 21).unwrap(), tromso),
+            None
+        );
+        assert!(sun_times(NaiveDate::from_ymd_opt(2024, 3, 21).unwrap(), tromso).is_some());
+    }
+}
@@ -0,0 +1,178 @@
+//! Placement and look of the panels drawn over the background.
+
+use crate::fonts::heading_family;
+
+use egui::style::Margin;
+use egui::{Align, Align2, Color32, FontId, Frame, Rounding, Style, TextStyle, Ui, Vec2};
+
+/// Where on the screen a panel is placed.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    pub fn align(self) -> Align2 {
+        match self {
+            Anchor::TopLeft => Align2::LEFT_TOP,
+            Anchor::Top => Align2::CENTER_TOP,
+            Anchor::TopRight => Align2::RIGHT_TOP,
+            Anchor::Left => Align2::LEFT_CENTER,
+            Anchor::Center => Align2::CENTER_CENTER,
+            Anchor::Right => Align2::RIGHT_CENTER,
+            Anchor::BottomLeft => Align2::LEFT_BOTTOM,
+            Anchor::Bottom => Align2::CENTER_BOTTOM,
+            Anchor::BottomRight => Align2::RIGHT_BOTTOM,
+        }
+    }
+}
+
+/// Parses a colour written as "#RRGGBB" or "#RRGGBBAA".
+pub fn parse_colour(text: &str) -> Result<[u8; 4], String> {
+    let invalid = || format!("Expected a colour as #RRGGBB or #RRGGBBAA, got {:?}", text);
+
+    let hex = text.trim().strip_prefix('#').ok_or_else(invalid)?;
+    if (hex.len() != 6 && hex.len() != 8) || !hex.is_ascii() {
+        return Err(invalid());
+    }
+
+    let mut colour = [255; 4];
+    for (i, channel) in colour.iter_mut().enumerate().take(hex.len() / 2) {
+        *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+
+    Ok(colour)
+}
+
+/// How a panel looks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PanelStyle {
+    pub heading_size: f32,
+    pub text: Color32,
+    /// Fill behind the text, with the opacity already applied.
+    pub background: Color32,
+    pub padding: f32,
+    pub corner_radius: f32,
+}
+
+/// Where a panel goes and how it looks.
+#[derive(Clone, Debug)]
+pub struct Panel {
+    pub anchor: Align2,
+    pub offset: Vec2,
+    /// Distance between panels stacked on top of each other, e.g. notifications.
+    pub spacing: f32,
+    pub style: PanelStyle,
+}
+
+impl Panel {
+    /// The frame to draw the panel in. Panels without a background have no frame.
+    pub fn frame(&self, style: &Style) -> Frame {
+        if self.style.background == Color32::TRANSPARENT {
+            return Frame::none();
+        }
+
+        let mut frame = Frame::window(style);
+        frame.inner_margin = Margin::same(self.style.padding);
+        frame.rounding = Rounding::same(self.style.corner_radius);
+        frame.fill = self.style.background;
+        frame.stroke.color = self.style.background;
+        frame
+    }
+
+    /// Offset of the `index`th panel in a stack, which grows away from the edge
+    /// the panels are anchored to.
+    pub fn stacked(&self, index: usize) -> Vec2 {
+        let direction = if self.anchor.y() == Align::Max {
+            -1.0
+        } else {
+            1.0
+        };
+
+        self.offset + Vec2::new(0.0, direction * self.spacing * index as f32)
+    }
+
+    /// Sets the size and colour of text drawn in the panel.
+    pub fn apply(&self, ui: &mut Ui) {
+        ui.style_mut().text_styles.insert(
+            TextStyle::Heading,
+            FontId::new(self.style.heading_size, heading_family()),
+        );
+        ui.visuals_mut().override_text_color = Some(self.style.text);
+    }
+}
+
+/// All of the panels on screen.
+#[derive(Clone, Debug)]
+pub struct Panels {
+    pub day: Panel,
+    pub night: Panel,
+    /// Whether the Night panel wanders around the screen, rather than staying put.
+    pub night_moves: bool,
+    pub notifications: Panel,
+}
+
+/// Sets up egui's style for anything which isn't drawn in a panel, e.g. the
+/// temperature history page.
+pub fn apply_style(style: &mut Style, panel: &PanelStyle, body_size: f32) {
+    style.spacing.window_margin = Margin::same(panel.padding);
+    style.visuals.dark_mode = true;
+    style.visuals.window_rounding = Rounding::same(panel.corner_radius);
+    style.visuals.widgets.noninteractive.bg_fill = panel.background;
+    style.visuals.widgets.noninteractive.bg_stroke.color = panel.background;
+    style.visuals.widgets.noninteractive.fg_stroke.color = panel.text;
+    style.text_styles.insert(
+        TextStyle::Heading,
+        FontId::new(panel.heading_size, heading_family()),
+    );
+    style
+        .text_styles
+        .insert(TextStyle::Body, FontId::proportional(body_size));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_colours() {
+        assert_eq!(parse_colour("#141414"), Ok([20, 20, 20, 255]));
+        assert_eq!(parse_colour(" #FF8000dc "), Ok([255, 128, 0, 220]));
+        assert!(parse_colour("141414").is_err());
+        assert!(parse_colour("#1414").is_err());
+        assert!(parse_colour("#14141G").is_err());
+        assert!(parse_colour("#ééé").is_err());
+    }
+
+    #[test]
+    fn stacks_away_from_the_edge() {
+        let style = PanelStyle {
+            heading_size: 40.0,
+            text: Color32::WHITE,
+            background: Color32::TRANSPARENT,
+            padding: 15.0,
+            corner_radius: 6.0,
+        };
+        let mut panel = Panel {
+            anchor: Anchor::TopRight.align(),
+            offset: Vec2::new(-10.0, 50.0),
+            spacing: 120.0,
+            style,
+        };
+
+        assert_eq!(panel.stacked(2), Vec2::new(-10.0, 290.0));
+
+        panel.anchor = Anchor::BottomRight.align();
+        panel.offset = Vec2::new(-10.0, -10.0);
+        assert_eq!(panel.stacked(1), Vec2::new(-10.0, -130.0));
+    }
+}
@@ -0,0 +1,131 @@
+//! The `weather` subcommand, for checking weather configuration without starting the UI.
+
+use crate::clock::ClockFormat;
+use crate::config::{LeaffrontConfig, WeatherLocation};
+
+use leaffront_weather::{Weather, WeatherProviderConfig, WeatherProviderKind};
+
+/// Fetches weather once from every configured provider and prints the result.
+/// Returns false if any provider failed.
+pub fn check(config: &LeaffrontConfig) -> bool {
+    let locations = config.get_weather_locations();
+
+    if locations.is_empty() {
+        println!("No weather locations are configured.");
+        return false;
+    }
+
+    let clock = config.clock.get_format(config.get_locale());
+    let mut success = true;
+
+    for (i, location) in locations.iter().enumerate() {
+        let name = location
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("Location {}", i + 1));
+
+        for provider in location.get_providers() {
+            println!("{} ({:?}):", name, provider.kind);
+
+            match provider.get_weather() {
+                Ok(weather) => print_weather(&weather, &clock),
+                Err(e) => {
+                    success = false;
+                    println!("  Error: {}", e);
+                    if e.is_permanent() {
+                        println!("  This won't fix itself - check the provider's config.");
+                    }
+                }
+            }
+
+            println!();
+        }
+    }
+
+    success
+}
+
+/// Searches for locations matching some text and prints them. Uses the given provider
+/// if set, otherwise the first configured one.
+pub fn search(config: &LeaffrontConfig, kind: Option<WeatherProviderKind>, text: &str) -> bool {
+    let provider = find_provider(&config.get_weather_locations(), kind);
+
+    let matches = match provider.search_locations(text) {
+        Ok(matches) => matches,
+        Err(e) => {
+            println!("Error: {}", e);
+            return false;
+        }
+    };
+
+    if matches.is_empty() {
+        println!("No {:?} locations match {:?}.", provider.kind, text);
+    }
+
+    for location in matches {
+        println!("{}", location);
+    }
+
+    true
+}
+
+/// Picks the configuration to search with - searches may need an API key or endpoint.
+fn find_provider(
+    locations: &[WeatherLocation],
+    kind: Option<WeatherProviderKind>,
+) -> WeatherProviderConfig {
+    let mut providers = locations.iter().flat_map(WeatherLocation::get_providers);
+
+    let provider = match kind {
+        Some(kind) => providers.find(|x| x.kind == kind),
+        None => providers.next(),
+    };
+
+    provider.unwrap_or_else(|| WeatherProviderConfig {
+        kind: kind.unwrap_or(WeatherProviderKind::BOM),
+        config: None,
+    })
+}
+
+fn print_weather(weather: &Weather, clock: &ClockFormat) {
+    println!(
+        "  Now: {:.1}°C - {}",
+        weather.temperature, weather.description
+    );
+
+    for forecast in &weather.daily {
+        let mut line = format!("  {}:", clock.format(forecast.date, "%a %-d %b"));
+
+        if let Some(min) = forecast.temp_min {
+            line += &format!(" min {:.0}°C", min);
+        }
+        if let Some(max) = forecast.temp_max {
+            line += &format!(" max {:.0}°C", max);
+        }
+        if let Some(sunrise) = forecast.sunrise {
+            line += &format!(" sunrise {}", clock.short_time(sunrise));
+        }
+        if let Some(sunset) = forecast.sunset {
+            line += &format!(" sunset {}", clock.short_time(sunset));
+        }
+        if let Some(uv) = forecast.uv {
+            line += &format!(" UV {:.0} ({})", uv.max_index, uv.category);
+        }
+
+        println!("{}", line);
+    }
+
+    if !weather.hourly.is_empty() {
+        println!("  {} hourly forecasts", weather.hourly.len());
+    }
+
+    match &weather.warnings {
+        Some(warnings) if warnings.is_empty() => println!("  No active warnings"),
+        Some(warnings) => {
+            for warning in warnings {
+                println!("  Warning ({:?}): {}", warning.severity, warning.title);
+            }
+        }
+        None => println!("  Warnings unavailable"),
+    }
+}
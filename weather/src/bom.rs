@@ -1,17 +1,44 @@
 //! A BOM (Australia) frontend for the Weather API.
 #![allow(dead_code)]
 
-use crate::{Weather, WeatherProvider};
+use crate::{
+    Forecast, LocationMatch, UvCategory, UvIndex, WarningSeverity, Weather, WeatherError,
+    WeatherProvider, WeatherWarning,
+};
+
+use chrono::{DateTime, Utc};
 
 use reqwest::header;
 
-static ENDPOINT: &'static str = "https://api.weather.bom.gov.au/v1";
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+static DEFAULT_ENDPOINT: &str = "https://api.weather.bom.gov.au/v1";
+
+fn default_endpoint() -> String {
+    DEFAULT_ENDPOINT.to_string()
+}
 
 #[derive(Deserialize, Debug)]
 struct BOMConfig {
     /// Some location - will be looked up against BOM's API.
     /// e.g. "Sydney"
-    location: String,
+    #[serde(default)]
+    location: Option<String>,
+    /// An explicit BOM geohash, e.g. "r3gx2f9". Skips the location search entirely.
+    #[serde(default)]
+    geohash: Option<String>,
+    /// Only accept search results with this postcode. Searched for directly
+    /// if no `location` is given.
+    #[serde(default)]
+    postcode: Option<String>,
+    /// Only accept search results in this state, e.g. "NSW".
+    #[serde(default)]
+    state: Option<String>,
+    /// Base URL of the BOM API, without a trailing slash. Override to
+    /// go through a caching proxy or a local mock.
+    #[serde(default = "default_endpoint")]
+    endpoint: String,
 }
 
 /// Metadata tag on JSON responses
@@ -60,10 +87,32 @@ struct ResponseWeatherNow {
     temp_later: Option<f64>,
 }
 
+/// Daily UV forecast
+#[derive(Deserialize, Debug)]
+struct ResponseUV {
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    max_index: Option<f64>,
+    #[serde(default)]
+    start_time: Option<String>,
+    #[serde(default)]
+    end_time: Option<String>,
+}
+
+/// Sun times for a day
+#[derive(Deserialize, Debug)]
+struct ResponseAstronomical {
+    #[serde(default)]
+    sunrise_time: Option<String>,
+    #[serde(default)]
+    sunset_time: Option<String>,
+}
+
 /// A single weather response from the API
 #[derive(Deserialize, Debug)]
 struct ResponseWeather {
-    // "rain", "uv", "astronomical" ignored
+    // "rain" ignored
     date: String,
     #[serde(default)]
     temp_max: Option<f64>,
@@ -79,6 +128,57 @@ struct ResponseWeather {
     fire_danger: Option<String>,
     #[serde(default)]
     now: Option<ResponseWeatherNow>,
+    #[serde(default)]
+    uv: Option<ResponseUV>,
+    #[serde(default)]
+    astronomical: Option<ResponseAstronomical>,
+}
+
+/// Parses one of BOM's ISO 8601 timestamps.
+fn parse_time(time: &str) -> Option<DateTime<Utc>> {
+    match DateTime::parse_from_rfc3339(time) {
+        Ok(time) => Some(time.with_timezone(&Utc)),
+        Err(e) => {
+            warn!("Failed to parse BOM timestamp {:?}: {:?}", time, e);
+            None
+        }
+    }
+}
+
+impl ResponseWeather {
+    fn to_forecast(&self) -> Option<Forecast> {
+        let astronomical = self.astronomical.as_ref();
+
+        let uv = self.uv.as_ref().and_then(|uv| {
+            let max_index = uv.max_index?;
+            let category = match uv.category.as_deref() {
+                Some("low") => UvCategory::Low,
+                Some("moderate") => UvCategory::Moderate,
+                Some("high") => UvCategory::High,
+                Some("veryhigh") => UvCategory::VeryHigh,
+                Some("extreme") => UvCategory::Extreme,
+                _ => UvCategory::from_index(max_index),
+            };
+
+            Some(UvIndex {
+                max_index,
+                category,
+            })
+        });
+
+        Some(Forecast {
+            date: parse_time(&self.date)?,
+            temp_min: self.temp_min,
+            temp_max: self.temp_max,
+            sunrise: astronomical
+                .and_then(|x| x.sunrise_time.as_deref())
+                .and_then(parse_time),
+            sunset: astronomical
+                .and_then(|x| x.sunset_time.as_deref())
+                .and_then(parse_time),
+            uv,
+        })
+    }
 }
 
 /// The entire forecasts query
@@ -106,11 +206,237 @@ struct ResponseObservations {
     data: ResponseObservationsPayload,
 }
 
+/// A single active warning
+#[derive(Deserialize, Debug)]
+struct ResponseWarning {
+    id: String,
+    #[serde(rename = "type")]
+    #[serde(default)]
+    warning_type: Option<String>,
+    title: String,
+    #[serde(default)]
+    short_title: Option<String>,
+    #[serde(default)]
+    state: Option<String>,
+    #[serde(default)]
+    warning_group_type: Option<String>,
+    #[serde(default)]
+    issue_time: Option<String>,
+    #[serde(default)]
+    expiry_time: Option<String>,
+    #[serde(default)]
+    phase: Option<String>,
+}
+
+/// All active warnings for a location
+#[derive(Deserialize, Debug)]
+struct ResponseWarnings {
+    metadata: ResponseMetadata,
+    data: Vec<ResponseWarning>,
+}
+
+impl ResponseLocation {
+    /// Checks this location against the configured postcode and state filters.
+    fn matches(&self, config: &BOMConfig) -> bool {
+        let postcode_matches = match &config.postcode {
+            Some(postcode) => self.postcode.as_deref() == Some(postcode.trim()),
+            None => true,
+        };
+
+        let state_matches = match &config.state {
+            Some(state) => self
+                .state
+                .as_deref()
+                .is_some_and(|x| x.eq_ignore_ascii_case(state.trim())),
+            None => true,
+        };
+
+        postcode_matches && state_matches
+    }
+
+    /// Human-readable summary, for disambiguating search results in logs.
+    fn describe(&self) -> String {
+        format!(
+            "{}, {} {} ({})",
+            self.name,
+            self.state.as_deref().unwrap_or("?"),
+            self.postcode.as_deref().unwrap_or("?"),
+            self.geohash
+        )
+    }
+}
+
+fn describe_locations<'a, I>(locations: I) -> String
+where
+    I: Iterator<Item = &'a ResponseLocation>,
+{
+    locations
+        .map(ResponseLocation::describe)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// A location which has been resolved to a geohash.
+#[derive(Clone, Debug)]
+struct ResolvedLocation {
+    geohash: String,
+    name: String,
+}
+
+/// Locations which have already been looked up, keyed by endpoint and search
+/// parameters. These don't change, so are kept for the lifetime of the process.
+static RESOLVED_LOCATIONS: Mutex<Option<HashMap<String, ResolvedLocation>>> = Mutex::new(None);
+
 static MIN_GEOCODE_LENGTH: usize = 4;
 
 pub struct BOM;
 
-impl BOM {}
+impl BOM {
+    /// Works out which geohash to use for a configuration, searching BOM's
+    /// locations API if needed. Successful searches are cached.
+    fn resolve_location(
+        client: &reqwest::blocking::Client,
+        endpoint: &str,
+        config: &BOMConfig,
+    ) -> Result<ResolvedLocation, WeatherError> {
+        if let Some(geohash) = &config.geohash {
+            return Ok(ResolvedLocation {
+                geohash: geohash.trim().to_string(),
+                name: geohash.trim().to_string(),
+            });
+        }
+
+        let search = config
+            .location
+            .as_ref()
+            .or(config.postcode.as_ref())
+            .ok_or_else(|| {
+                WeatherError::Config(
+                    "BOM config needs one of location, postcode or geohash".to_string(),
+                )
+            })?;
+
+        let key = format!(
+            "{}|{}|{:?}|{:?}",
+            endpoint, search, config.postcode, config.state
+        );
+
+        if let Some(location) = RESOLVED_LOCATIONS
+            .lock()
+            .expect("Failed to lock BOM location cache")
+            .as_ref()
+            .and_then(|x| x.get(&key))
+        {
+            return Ok(location.clone());
+        }
+
+        let location = Self::search_location(client, endpoint, search, config)?;
+
+        RESOLVED_LOCATIONS
+            .lock()
+            .expect("Failed to lock BOM location cache")
+            .get_or_insert_with(HashMap::new)
+            .insert(key, location.clone());
+
+        Ok(location)
+    }
+
+    /// Builds a client which identifies itself to BOM.
+    fn client() -> Result<reqwest::blocking::Client, WeatherError> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::USER_AGENT,
+            header::HeaderValue::from_static(concat!(
+                "LeafFront/v",
+                env!("CARGO_PKG_VERSION"),
+                " (https://github.com/j-selby/leaffront)"
+            )),
+        );
+
+        reqwest::blocking::Client::builder()
+            .default_headers(headers)
+            .build()
+            .map_err(|x| {
+                WeatherError::Transient(format!("Failed to build reqwest client: {:?}", x))
+            })
+    }
+
+    /// Queries BOM's locations API for some free text.
+    fn fetch_locations(
+        client: &reqwest::blocking::Client,
+        endpoint: &str,
+        search: &str,
+    ) -> Result<ResponseLocations, WeatherError> {
+        client
+            .get(format!("{}/locations", endpoint))
+            .query(&[("search", search)])
+            .send()
+            .map_err(|x| {
+                WeatherError::Transient(format!("Failed to handle locations request: {:?}", x))
+            })?
+            .json()
+            .map_err(|x| {
+                WeatherError::Transient(format!("Failed to parse BOM weather response: {:?}", x))
+            })
+    }
+
+    /// Searches BOM's locations API, picking the first result which matches
+    /// the configured filters.
+    fn search_location(
+        client: &reqwest::blocking::Client,
+        endpoint: &str,
+        search: &str,
+        config: &BOMConfig,
+    ) -> Result<ResolvedLocation, WeatherError> {
+        let locations_response = Self::fetch_locations(client, endpoint, search)?;
+
+        if locations_response.data.is_empty() {
+            return Err(WeatherError::Config(format!(
+                "No response for location of {:?}",
+                search
+            )));
+        }
+
+        let candidates: Vec<&ResponseLocation> = locations_response
+            .data
+            .iter()
+            .filter(|x| x.matches(config))
+            .collect();
+
+        let location_info = match candidates.first() {
+            Some(location) => *location,
+            None => {
+                return Err(WeatherError::Config(format!(
+                    "No location for {:?} matched postcode {:?} and state {:?}. Candidates: {}",
+                    search,
+                    config.postcode,
+                    config.state,
+                    describe_locations(locations_response.data.iter())
+                )))
+            }
+        };
+
+        if candidates.len() > 1 {
+            warn!(
+                "Returned multiple locations for {:?}, continuing with first. \
+                 Set a postcode, state or geohash to pick another: {}",
+                search,
+                describe_locations(candidates.iter().cloned())
+            );
+        }
+
+        info!(
+            "Got {} for location request of {:?}",
+            location_info.describe(),
+            search
+        );
+
+        Ok(ResolvedLocation {
+            geohash: location_info.geohash.clone(),
+            name: location_info.name.clone(),
+        })
+    }
+}
 
 fn try_with_different_length_geocodes<T, F>(
     client: &reqwest::blocking::Client,
@@ -151,94 +477,134 @@ where
 }
 
 impl WeatherProvider for BOM {
-    fn get_weather(config: Option<toml::Value>) -> Result<Weather, String> {
+    fn get_weather(config: Option<toml::Value>) -> Result<Weather, WeatherError> {
         // We require a config for BOM:
-        let config = config.ok_or_else(|| "BOM configuration needed".to_string())?;
+        let config =
+            config.ok_or_else(|| WeatherError::Config("BOM configuration needed".to_string()))?;
 
         // Parse into a configuration type
         let config: BOMConfig = config
             .try_into()
-            .map_err(|x| format!("Failed to parse BOM config: {:?}", x))?;
-
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            header::USER_AGENT,
-            header::HeaderValue::from_static(concat!(
-                "LeafFront/v",
-                env!("CARGO_PKG_VERSION"),
-                " (https://github.com/j-selby/leaffront)"
-            )),
-        );
+            .map_err(|x| WeatherError::Config(format!("Failed to parse BOM config: {:?}", x)))?;
 
-        let client = reqwest::blocking::Client::builder()
-            .default_headers(headers)
-            .build()
-            .map_err(|x| format!("Failed to build reqwest client: {:?}", x))?;
+        let client = Self::client()?;
 
-        let locations_response: ResponseLocations = client
-            .get(&format!("{}/locations", ENDPOINT))
-            .query(&[("search", config.location.clone())])
-            .send()
-            .map_err(|x| format!("Failed to handle locations request: {:?}", x))?
-            .json()
-            .map_err(|x| format!("Failed to parse BOM weather response: {:?}", x))?;
+        let endpoint = config.endpoint.trim_end_matches('/');
 
-        // Attempt to see if we actually have a response
-        if locations_response.data.len() > 1 {
-            warn!(
-                "Returned multiple locations for {:?}, continuing with first:",
-                config.location
-            );
-        }
-
-        let location_info = locations_response
-            .data
-            .get(0)
-            .ok_or_else(|| format!("No response for location of {:?}", config.location))?;
-
-        info!(
-            "Got {:?} for location request of {:?}",
-            location_info.name, config.location
-        );
+        let location_info = Self::resolve_location(&client, endpoint, &config)?;
 
         // Attempt with the full geohash, then retry with regional info
         let weather_response: ResponseForecast = try_with_different_length_geocodes(
             &client,
-            |geohash| format!("{}/locations/{}/forecasts/daily", ENDPOINT, geohash),
+            |geohash| format!("{}/locations/{}/forecasts/daily", endpoint, geohash),
             &location_info.geohash,
         )
-        .map_err(|x| format!("Failed to download BOM forecasts weather response: {:?}", x))?;
+        .map_err(|x| {
+            WeatherError::Transient(format!(
+                "Failed to download BOM forecasts weather response: {:?}",
+                x
+            ))
+        })?;
 
         // Attempt to get the first element of the forecast
-        let weather_entry = weather_response
-            .data
-            .get(0)
-            .ok_or_else(|| format!("No weather for location of {:?}", config.location))?;
+        let weather_entry = weather_response.data.first().ok_or_else(|| {
+            WeatherError::Transient(format!(
+                "No weather for location of {:?}",
+                location_info.name
+            ))
+        })?;
 
         let description = weather_entry.short_text.clone().ok_or_else(|| {
-            format!(
+            WeatherError::Transient(format!(
                 "No current weather description for location of {:?}",
-                config.location
-            )
+                location_info.name
+            ))
         })?;
 
         let observations_response: ResponseObservations = try_with_different_length_geocodes(
             &client,
-            |geohash| format!("{}/locations/{}/observations", ENDPOINT, geohash),
+            |geohash| format!("{}/locations/{}/observations", endpoint, geohash),
             &location_info.geohash,
         )
         .map_err(|x| {
-            format!(
+            WeatherError::Transient(format!(
                 "Failed to download BOM observations weather response: {:?}",
                 x
-            )
+            ))
         })?;
 
+        // Warnings are best-effort - failing to fetch them shouldn't hide the weather.
+        let warnings = match try_with_different_length_geocodes::<ResponseWarnings, _>(
+            &client,
+            |geohash| format!("{}/locations/{}/warnings", endpoint, geohash),
+            &location_info.geohash,
+        ) {
+            Ok(response) => Some(
+                response
+                    .data
+                    .into_iter()
+                    // Cancelled warnings are still listed until they expire
+                    .filter(|x| x.phase.as_deref() != Some("cancelled"))
+                    .map(|x| WeatherWarning {
+                        id: x.id,
+                        title: x.title,
+                        severity: match x.warning_group_type.as_deref() {
+                            Some("major") => WarningSeverity::Major,
+                            _ => WarningSeverity::Minor,
+                        },
+                        expiry_time: x.expiry_time.as_deref().and_then(parse_time),
+                    })
+                    .collect(),
+            ),
+            Err(e) => {
+                warn!("Failed to download BOM warnings: {:?}", e);
+                None
+            }
+        };
+
         info!("Downloaded weather from BOM successfully");
 
+        let daily = weather_response
+            .data
+            .iter()
+            .filter_map(ResponseWeather::to_forecast)
+            .collect();
+
         Ok(Weather {
             temperature: observations_response.data.temp,
             description,
+            daily,
+            hourly: Vec::new(),
+            warnings,
         })
     }
+
+    fn search_locations(
+        config: Option<toml::Value>,
+        text: &str,
+    ) -> Result<Vec<LocationMatch>, WeatherError> {
+        // Only the endpoint is needed here, so the config is optional
+        let config: BOMConfig = config
+            .unwrap_or_else(|| toml::Value::Table(Default::default()))
+            .try_into()
+            .map_err(|x| WeatherError::Config(format!("Failed to parse BOM config: {:?}", x)))?;
+
+        let client = Self::client()?;
+
+        let response = Self::fetch_locations(&client, config.endpoint.trim_end_matches('/'), text)?;
+
+        Ok(response
+            .data
+            .into_iter()
+            .map(|x| LocationMatch {
+                name: x.name,
+                region: x.state,
+                postcode: x.postcode,
+                country: None,
+                geohash: Some(x.geohash),
+                lat: None,
+                lon: None,
+            })
+            .collect())
+    }
 }
@@ -4,7 +4,9 @@ extern crate reqwest;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate chrono;
 extern crate inflector;
+extern crate rand;
 extern crate toml;
 #[macro_use]
 extern crate log;
@@ -13,19 +15,243 @@ pub mod bom;
 pub mod manager;
 pub mod openweathermap;
 
-#[derive(Clone)]
+use chrono::{DateTime, Utc};
+
+use std::fmt;
+
+#[derive(Clone, Debug)]
 pub struct Weather {
     pub temperature: f64,
     pub description: String,
+    /// Outlook for upcoming days, starting with today. May be empty if the
+    /// provider doesn't offer forecasts.
+    pub daily: Vec<Forecast>,
+    /// Outlook for the coming hours, soonest first. May be empty if the
+    /// provider doesn't offer hourly forecasts.
+    pub hourly: Vec<HourlyForecast>,
+    /// Active severe weather warnings. `None` if the provider doesn't support
+    /// warnings or they couldn't be fetched this time around.
+    pub warnings: Option<Vec<WeatherWarning>>,
+}
+
+/// The outlook for a single day
+#[derive(Clone, Debug, PartialEq)]
+pub struct Forecast {
+    /// The start of the day this forecast covers.
+    pub date: DateTime<Utc>,
+    pub temp_min: Option<f64>,
+    pub temp_max: Option<f64>,
+    pub sunrise: Option<DateTime<Utc>>,
+    pub sunset: Option<DateTime<Utc>>,
+    pub uv: Option<UvIndex>,
+}
+
+/// The outlook for a single hour
+#[derive(Clone, Debug, PartialEq)]
+pub struct HourlyForecast {
+    /// The start of the hour this forecast covers.
+    pub time: DateTime<Utc>,
+    pub temperature: f64,
+    pub description: Option<String>,
+}
+
+/// The peak UV index expected for a day
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UvIndex {
+    pub max_index: f64,
+    pub category: UvCategory,
+}
+
+/// WHO UV exposure categories
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UvCategory {
+    Low,
+    Moderate,
+    High,
+    VeryHigh,
+    Extreme,
+}
+
+impl UvCategory {
+    /// Categorises a raw UV index.
+    pub fn from_index(index: f64) -> Self {
+        match index.round() as i64 {
+            i64::MIN..=2 => UvCategory::Low,
+            3..=5 => UvCategory::Moderate,
+            6..=7 => UvCategory::High,
+            8..=10 => UvCategory::VeryHigh,
+            _ => UvCategory::Extreme,
+        }
+    }
+}
+
+impl fmt::Display for UvCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            UvCategory::Low => "Low",
+            UvCategory::Moderate => "Moderate",
+            UvCategory::High => "High",
+            UvCategory::VeryHigh => "Very high",
+            UvCategory::Extreme => "Extreme",
+        })
+    }
+}
+
+/// How urgent a warning is
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WarningSeverity {
+    Minor,
+    Major,
+}
+
+/// A severe weather warning issued by a provider
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WeatherWarning {
+    /// Provider-specific identifier, stable across updates to the same warning.
+    pub id: String,
+    pub title: String,
+    pub severity: WarningSeverity,
+    /// When the warning lapses, as reported by the provider.
+    pub expiry_time: Option<DateTime<Utc>>,
+}
+
+impl WeatherWarning {
+    /// Returns true if the warning hasn't lapsed. Warnings without an expiry time
+    /// never lapse by themselves.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.expiry_time.is_none_or(|x| x > now)
+    }
+}
+
+/// Why a provider failed to return weather
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WeatherError {
+    /// The provider configuration is missing or invalid. Retrying won't help.
+    Config(String),
+    /// The provider rejected our credentials. Retrying won't help.
+    Auth(String),
+    /// A network, server or parsing failure which may clear up by itself.
+    Transient(String),
+}
+
+impl WeatherError {
+    /// Returns true if retrying the same request is pointless.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            WeatherError::Config(_) | WeatherError::Auth(_) => true,
+            WeatherError::Transient(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for WeatherError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WeatherError::Config(msg) => write!(f, "Weather configuration error: {}", msg),
+            WeatherError::Auth(msg) => write!(f, "Weather authentication error: {}", msg),
+            WeatherError::Transient(msg) => f.write_str(msg),
+        }
+    }
+}
+
+/// A place returned by a provider's location search
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocationMatch {
+    pub name: String,
+    /// State or region, if known.
+    pub region: Option<String>,
+    pub postcode: Option<String>,
+    pub country: Option<String>,
+    /// BOM's identifier for the location.
+    pub geohash: Option<String>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+}
+
+impl fmt::Display for LocationMatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)?;
+
+        let details: Vec<&str> = [&self.region, &self.postcode, &self.country]
+            .iter()
+            .filter_map(|x| x.as_deref())
+            .collect();
+        if !details.is_empty() {
+            write!(f, ", {}", details.join(" "))?;
+        }
+
+        if let Some(geohash) = &self.geohash {
+            write!(f, " (geohash {})", geohash)?;
+        }
+        if let (Some(lat), Some(lon)) = (self.lat, self.lon) {
+            write!(f, " (lat {}, lon {})", lat, lon)?;
+        }
+
+        Ok(())
+    }
 }
 
 pub trait WeatherProvider {
-    fn get_weather(config: Option<toml::Value>) -> Result<Weather, String>;
+    fn get_weather(config: Option<toml::Value>) -> Result<Weather, WeatherError>;
+
+    /// Looks up places matching some free text, for working out what to put
+    /// in a configuration.
+    fn search_locations(
+        _config: Option<toml::Value>,
+        _text: &str,
+    ) -> Result<Vec<LocationMatch>, WeatherError> {
+        Err(WeatherError::Config(
+            "This provider doesn't support location search".to_string(),
+        ))
+    }
 }
 
 /// What weather providers are available:
-#[derive(Copy, Clone, Deserialize, Debug)]
+#[derive(Copy, Clone, Deserialize, Debug, PartialEq, Eq)]
 pub enum WeatherProviderKind {
     OpenWeatherMap,
     BOM,
 }
+
+/// A provider along with its configuration
+#[derive(Clone, Deserialize, Debug)]
+pub struct WeatherProviderConfig {
+    pub kind: WeatherProviderKind,
+    #[serde(default)]
+    pub config: Option<toml::Value>,
+}
+
+impl WeatherProviderConfig {
+    /// Fetches weather from this provider.
+    pub fn get_weather(&self) -> Result<Weather, WeatherError> {
+        match self.kind {
+            WeatherProviderKind::OpenWeatherMap => {
+                openweathermap::OpenWeatherMap::get_weather(self.config.clone())
+            }
+            WeatherProviderKind::BOM => bom::BOM::get_weather(self.config.clone()),
+        }
+    }
+
+    /// Searches this provider for places matching some free text.
+    pub fn search_locations(&self, text: &str) -> Result<Vec<LocationMatch>, WeatherError> {
+        match self.kind {
+            WeatherProviderKind::OpenWeatherMap => {
+                openweathermap::OpenWeatherMap::search_locations(self.config.clone(), text)
+            }
+            WeatherProviderKind::BOM => bom::BOM::search_locations(self.config.clone(), text),
+        }
+    }
+
+    /// Asks for descriptions in the given language (e.g. "de-DE"), if the provider
+    /// supports it and no language was configured explicitly.
+    pub fn set_language(&mut self, tag: &str) {
+        // The BOM only publishes in English
+        if let (WeatherProviderKind::OpenWeatherMap, Some(toml::Value::Table(table))) =
+            (self.kind, &mut self.config)
+        {
+            table
+                .entry("lang")
+                .or_insert_with(|| toml::Value::String(openweathermap::language_code(tag)));
+        }
+    }
+}
@@ -1,57 +1,183 @@
 /// The weather manager controls a weather polling thread, and provides a mechanism to poll
 /// for weather whenever required.
-use crate::{Weather, WeatherProviderKind};
-
-use crate::WeatherProvider;
+use crate::{Weather, WeatherError, WeatherProviderConfig, WeatherWarning};
 
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 
 use std::thread;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+
+use rand::Rng;
+
+/// How long a single provider request may take before it is given up on.
+static REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Controls how failed updates are retried.
+#[derive(Copy, Clone, Debug)]
+pub struct RetryPolicy {
+    /// Delay after the first failure. Doubles for each consecutive failure.
+    pub initial_delay: Duration,
+    /// Upper bound on the delay between retries.
+    pub max_delay: Duration,
+    /// Consecutive transient failures before failing over to the next provider.
+    pub failover_after: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            initial_delay: Duration::from_secs(10),
+            max_delay: Duration::from_secs(30 * 60),
+            failover_after: 3,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the next attempt, given how many consecutive failures
+    /// have occurred so far. `jitter` is in the range [0, 1), and scales the delay to
+    /// between 50% and 100% of the exponential value so that frames don't retry in step.
+    pub fn delay(&self, failures: u32, jitter: f64) -> Duration {
+        let exponent = failures.saturating_sub(1).min(31);
+        let delay = self
+            .initial_delay
+            .checked_mul(1 << exponent)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+
+        delay.mul_f64(0.5 + jitter.clamp(0.0, 1.0) / 2.0)
+    }
+}
+
+/// Decides which provider to poll next, based on the outcome of previous polls.
+struct ProviderChain {
+    policy: RetryPolicy,
+    current: usize,
+    /// Failures of the current provider, which decide when to fail over.
+    failures: u32,
+    /// Failures across all providers since the last success, which decide the delay.
+    consecutive: u32,
+    disabled: Vec<bool>,
+}
+
+impl ProviderChain {
+    fn new(providers: usize, policy: RetryPolicy) -> Self {
+        ProviderChain {
+            policy,
+            current: 0,
+            failures: 0,
+            consecutive: 0,
+            disabled: vec![false; providers],
+        }
+    }
+
+    /// The provider to poll next, or None if every provider has been disabled.
+    fn current(&self) -> Option<usize> {
+        if self.disabled.get(self.current) == Some(&false) {
+            Some(self.current)
+        } else {
+            None
+        }
+    }
+
+    /// Finds the next enabled provider after the current one, wrapping around.
+    fn next_enabled(&self) -> Option<usize> {
+        let count = self.disabled.len();
+        (1..=count)
+            .map(|offset| (self.current + offset) % count)
+            .find(|&i| !self.disabled[i])
+    }
+
+    /// Records a successful poll. The next poll goes back to the preferred provider.
+    fn on_success(&mut self) {
+        self.failures = 0;
+        self.consecutive = 0;
+        self.current = self.disabled.iter().position(|x| !x).unwrap_or(0);
+    }
+
+    /// Records a failed poll, returning how long to wait before polling again. Returns
+    /// None if there are no providers left to try.
+    fn on_failure(&mut self, error: &WeatherError, jitter: f64) -> Option<Duration> {
+        if error.is_permanent() {
+            self.disabled[self.current] = true;
+            self.failures = 0;
+            self.current = self.next_enabled()?;
+            return Some(Duration::from_secs(0));
+        }
 
-use crate::openweathermap::OpenWeatherMap;
+        self.failures += 1;
+        self.consecutive += 1;
 
-use crate::bom::BOM;
+        if self.failures >= self.policy.failover_after {
+            if let Some(next) = self.next_enabled() {
+                if next != self.current {
+                    self.current = next;
+                    self.failures = 0;
+                    return Some(Duration::from_secs(0));
+                }
+            }
+        }
+
+        Some(self.policy.delay(self.consecutive, jitter))
+    }
+}
 
 struct WeatherWorker {
-    channel_sender: Sender<()>,
-    channel_receiver: Receiver<Result<Weather, String>>,
+    channel_sender: Sender<(u64, usize)>,
+    channel_receiver: Receiver<(u64, Result<Weather, WeatherError>)>,
+    next_request: u64,
 }
 
 impl WeatherWorker {
-    pub fn send_request(&self) {
+    /// Asks the worker to poll a provider, returning an ID for the request.
+    pub fn send_request(&mut self, provider: usize) -> u64 {
+        let request = self.next_request;
+        self.next_request += 1;
+
         self.channel_sender
-            .send(())
-            .expect("Failed to send message!")
+            .send((request, provider))
+            .expect("Failed to send message!");
+
+        request
     }
 
+    /// Waits for the reply to a request. Late replies to earlier requests which
+    /// timed out are dropped.
     pub fn wait_for_request(
         &self,
+        request: u64,
         timeout: Duration,
-    ) -> Result<Result<Weather, String>, RecvTimeoutError> {
-        self.channel_receiver.recv_timeout(timeout)
+    ) -> Result<Result<Weather, WeatherError>, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.channel_receiver.recv_timeout(timeout)? {
+                (id, weather) if id == request => return Ok(weather),
+                _ => {}
+            }
+        }
     }
 
-    pub fn new(kind: WeatherProviderKind, config: Option<toml::Value>) -> Self {
+    pub fn new(providers: Vec<WeatherProviderConfig>) -> Self {
         let (request_tx, request_rx) = mpsc::channel();
         let (response_tx, response_rx) = mpsc::channel();
 
         thread::spawn(move || {
             'main_loop: loop {
                 match request_rx.recv() {
-                    Ok(_) => {
-                        // We have a weather request - service it.
-                        let weather = match kind {
-                            WeatherProviderKind::OpenWeatherMap => {
-                                OpenWeatherMap::get_weather(config.clone())
-                            }
-                            WeatherProviderKind::BOM => BOM::get_weather(config.clone()),
-                        };
-                        response_tx
-                            .send(weather)
-                            .expect("Failed to send weather to weather control thread");
+                    Ok(request) => {
+                        // Requests which queued up behind a slow one have already
+                        // timed out, so only service the latest.
+                        let (id, index) = request_rx.try_iter().last().unwrap_or(request);
+                        let provider: &WeatherProviderConfig = &providers[index];
+                        let weather = provider.get_weather();
+                        // Nobody may be waiting for this any more
+                        let _ = response_tx.send((id, weather));
                     }
                     Err(_) => {
                         // We were disconnected?
@@ -64,75 +190,156 @@ impl WeatherWorker {
         WeatherWorker {
             channel_sender: request_tx,
             channel_receiver: response_rx,
+            next_request: 0,
         }
     }
 }
 
 pub struct WeatherManager {
-    input: Receiver<Result<Weather, String>>,
-    current: Option<Result<Weather, String>>,
+    input: Receiver<Result<Weather, WeatherError>>,
+    current: Option<Result<Weather, WeatherError>>,
+    fresh: Option<Weather>,
+    warnings: Vec<WeatherWarning>,
 }
 
 impl WeatherManager {
-    /// Gets the latest weather information.
-    pub fn get(&mut self) -> Result<Weather, String> {
+    /// Pulls in any updates from the polling thread.
+    fn update(&mut self) {
         for result in self.input.try_iter() {
+            match result {
+                Ok(Weather {
+                    warnings: Some(ref warnings),
+                    ..
+                }) => self.warnings = warnings.clone(),
+                // The provider can't confirm warnings are still in effect, so only
+                // keep those which will lapse by themselves
+                Ok(Weather { warnings: None, .. }) => {
+                    self.warnings.retain(|x| x.expiry_time.is_some())
+                }
+                Err(_) => {}
+            }
+
+            if let Ok(ref weather) = result {
+                self.fresh = Some(weather.clone());
+            }
+
             self.current = Some(result);
         }
+    }
+
+    /// Gets the currently active weather warnings. These are retained across failed
+    /// updates, and only cleared once the provider stops reporting them or they lapse.
+    pub fn get_warnings(&mut self) -> &[WeatherWarning] {
+        self.update();
+
+        let now = Utc::now();
+        self.warnings.retain(|x| x.is_active(now));
+
+        &self.warnings
+    }
+
+    /// Returns the latest weather if it has arrived since the last call.
+    pub fn take_update(&mut self) -> Option<Weather> {
+        self.update();
+
+        self.fresh.take()
+    }
+
+    /// Gets the latest weather information.
+    pub fn get(&mut self) -> Result<Weather, String> {
+        self.update();
 
         let data = self.current.clone();
 
         match data {
-            Some(weather) => weather,
+            Some(weather) => weather.map_err(|x| x.to_string()),
             None => Err("unavailable".into()),
         }
     }
 
     /// Creates a new manager with a dedicated thread.
     /// update_frequency: milliseconds between updates
+    /// providers: providers to try, in order of preference
     pub fn new(
         update_frequency: u64,
-        provider: WeatherProviderKind,
-        config: Option<toml::Value>,
+        providers: Vec<WeatherProviderConfig>,
+        retry_policy: RetryPolicy,
     ) -> Self {
         let (tx, rx) = mpsc::channel();
 
         thread::spawn(move || {
-            let mut worker = WeatherWorker::new(provider, config.clone());
+            let mut worker = WeatherWorker::new(providers.clone());
+            let mut chain = ProviderChain::new(providers.len(), retry_policy);
+            let mut rng = rand::thread_rng();
 
             loop {
-                worker.send_request();
+                let index = match chain.current() {
+                    Some(index) => index,
+                    None => {
+                        error!("No usable weather providers left; giving up on weather updates");
+                        break;
+                    }
+                };
+                let kind = providers[index].kind;
+
+                let request = worker.send_request(index);
+
+                let weather = match worker.wait_for_request(request, REQUEST_TIMEOUT) {
+                    Ok(weather) => weather,
+                    Err(e) => {
+                        // The request can't be cancelled, so its reply is dropped
+                        // whenever it turns up
+                        Err(WeatherError::Transient(format!(
+                            "Weather request timed out ({:?})",
+                            e
+                        )))
+                    }
+                };
 
-                match worker.wait_for_request(Duration::from_secs(10)) {
-                    // If polling worked fine
+                match weather {
                     Ok(weather) => {
-                        let success = weather.is_ok();
+                        chain.on_success();
+
+                        tx.send(Ok(weather))
+                            .expect("Failed to send weather to main thread");
+
+                        thread::sleep(Duration::from_millis(update_frequency));
+                    }
+                    Err(e) => {
+                        let delay = chain.on_failure(&e, rng.gen());
+
+                        if e.is_permanent() {
+                            error!("Weather provider {:?} disabled: {}", kind, e);
+                        } else {
+                            warn!("Weather update from {:?} failed: {}", kind, e);
+                        }
 
-                        match &weather {
-                            &Err(ref x) => {
-                                warn!("Weather update failed ({:?}); retrying in 10 seconds...", x);
+                        match (chain.current(), delay) {
+                            (Some(next), Some(delay)) if next != index => {
+                                info!(
+                                    "Failing over to weather provider {:?} in {:?}",
+                                    providers[next].kind, delay
+                                );
+                            }
+                            (Some(_), Some(delay)) => {
+                                info!("Retrying weather update in {:?}...", delay);
                             }
                             _ => {}
                         }
 
-                        tx.send(weather)
+                        tx.send(Err(e))
                             .expect("Failed to send weather to main thread");
 
-                        if success {
-                            thread::sleep(Duration::from_millis(update_frequency));
-                        } else {
-                            thread::sleep(Duration::from_millis(10 * 1000));
+                        match delay {
+                            Some(delay) => thread::sleep(delay),
+                            None => {
+                                error!(
+                                    "No usable weather providers left; giving up on weather updates"
+                                );
+                                break;
+                            }
                         }
                     }
-                    Err(e) => {
-                        warn!(
-                            "Weather thread timed out ({:?}); reinitialising and retrying in 10 seconds...",
-                            e
-                        );
-                        thread::sleep(Duration::from_millis(10 * 1000));
-
-                        worker = WeatherWorker::new(provider, config.clone());
-                    }
                 }
             }
         });
@@ -140,6 +347,193 @@ impl WeatherManager {
         WeatherManager {
             input: rx,
             current: None,
+            fresh: None,
+            warnings: Vec::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::WarningSeverity;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            initial_delay: Duration::from_secs(10),
+            max_delay: Duration::from_secs(60),
+            failover_after: 2,
+        }
+    }
+
+    fn transient() -> WeatherError {
+        WeatherError::Transient("timed out".to_string())
+    }
+
+    #[test]
+    fn delay_backs_off_exponentially() {
+        let policy = policy();
+
+        assert_eq!(policy.delay(1, 1.0), Duration::from_secs(10));
+        assert_eq!(policy.delay(2, 1.0), Duration::from_secs(20));
+        assert_eq!(policy.delay(3, 1.0), Duration::from_secs(40));
+        assert_eq!(policy.delay(4, 1.0), Duration::from_secs(60));
+        assert_eq!(policy.delay(100, 1.0), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn delay_applies_jitter() {
+        let policy = policy();
+
+        assert_eq!(policy.delay(1, 0.0), Duration::from_secs(5));
+        assert_eq!(policy.delay(2, 0.5), Duration::from_secs(15));
+    }
+
+    #[test]
+    fn retries_single_provider_forever() {
+        let mut chain = ProviderChain::new(1, policy());
+
+        for _ in 0..10 {
+            assert!(chain.on_failure(&transient(), 1.0).is_some());
+            assert_eq!(chain.current(), Some(0));
+        }
+    }
+
+    #[test]
+    fn fails_over_after_repeated_transient_errors() {
+        let mut chain = ProviderChain::new(2, policy());
+
+        assert_eq!(
+            chain.on_failure(&transient(), 1.0),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(chain.current(), Some(0));
+
+        assert_eq!(
+            chain.on_failure(&transient(), 1.0),
+            Some(Duration::from_secs(0))
+        );
+        assert_eq!(chain.current(), Some(1));
+
+        // Backoff carries on growing for the new provider
+        assert_eq!(
+            chain.on_failure(&transient(), 1.0),
+            Some(Duration::from_secs(40))
+        );
+        assert_eq!(chain.current(), Some(1));
+
+        // ... and wraps around to the first
+        assert_eq!(
+            chain.on_failure(&transient(), 1.0),
+            Some(Duration::from_secs(0))
+        );
+        assert_eq!(chain.current(), Some(0));
+        assert_eq!(
+            chain.on_failure(&transient(), 1.0),
+            Some(Duration::from_secs(60))
+        );
+
+        // ... until an update succeeds
+        chain.on_success();
+        assert_eq!(
+            chain.on_failure(&transient(), 1.0),
+            Some(Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn success_returns_to_preferred_provider() {
+        let mut chain = ProviderChain::new(2, policy());
+
+        chain.on_failure(&transient(), 1.0);
+        chain.on_failure(&transient(), 1.0);
+        assert_eq!(chain.current(), Some(1));
+
+        chain.on_success();
+        assert_eq!(chain.current(), Some(0));
+    }
+
+    #[test]
+    fn permanent_errors_disable_provider() {
+        let mut chain = ProviderChain::new(3, policy());
+
+        let auth = WeatherError::Auth("Invalid API key".to_string());
+        assert_eq!(chain.on_failure(&auth, 1.0), Some(Duration::from_secs(0)));
+        assert_eq!(chain.current(), Some(1));
+
+        // A disabled provider is skipped when failing over, and not returned to on
+        // success
+        chain.on_failure(&transient(), 1.0);
+        chain.on_failure(&transient(), 1.0);
+        assert_eq!(chain.current(), Some(2));
+        chain.on_failure(&transient(), 1.0);
+        chain.on_failure(&transient(), 1.0);
+        assert_eq!(chain.current(), Some(1));
+
+        chain.on_success();
+        assert_eq!(chain.current(), Some(1));
+    }
+
+    #[test]
+    fn drops_lapsed_warnings() {
+        let warning = |id: &str, expiry_time| WeatherWarning {
+            id: id.to_string(),
+            title: id.to_string(),
+            severity: WarningSeverity::Minor,
+            expiry_time,
+        };
+        let weather = |warnings| Weather {
+            temperature: 20.0,
+            description: "Sunny".to_string(),
+            daily: Vec::new(),
+            hourly: Vec::new(),
+            warnings,
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let mut manager = WeatherManager {
+            input: rx,
+            current: None,
+            fresh: None,
+            warnings: Vec::new(),
+        };
+
+        let soon = Utc::now() + chrono::Duration::hours(1);
+        let lapsed = Utc::now() - chrono::Duration::hours(1);
+        tx.send(Ok(weather(Some(vec![
+            warning("flood", Some(soon)),
+            warning("storm", Some(lapsed)),
+            warning("wind", None),
+        ]))))
+        .unwrap();
+
+        let ids = |manager: &mut WeatherManager| {
+            manager
+                .get_warnings()
+                .iter()
+                .map(|x| x.id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&mut manager), vec!["flood", "wind"]);
+
+        // Failures keep warnings, but a provider without warnings can't vouch for them
+        tx.send(Err(transient())).unwrap();
+        assert_eq!(ids(&mut manager), vec!["flood", "wind"]);
+        tx.send(Ok(weather(None))).unwrap();
+        assert_eq!(ids(&mut manager), vec!["flood"]);
+
+        tx.send(Ok(weather(Some(Vec::new())))).unwrap();
+        assert!(ids(&mut manager).is_empty());
+    }
+
+    #[test]
+    fn gives_up_when_all_providers_disabled() {
+        let mut chain = ProviderChain::new(2, policy());
+
+        let config = WeatherError::Config("Unknown location".to_string());
+        assert!(chain.on_failure(&config, 1.0).is_some());
+        assert_eq!(chain.on_failure(&config, 1.0), None);
+        assert_eq!(chain.current(), None);
+    }
+}
@@ -1,12 +1,38 @@
 //! Fetches weather from OpenWeatherMap
 #![allow(dead_code)]
 
+use crate::Forecast;
+use crate::HourlyForecast;
+use crate::LocationMatch;
+use crate::UvCategory;
+use crate::UvIndex;
+use crate::WarningSeverity;
 use crate::Weather;
+use crate::WeatherError;
 use crate::WeatherProvider;
+use crate::WeatherWarning;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
 
 use inflector::Inflector;
 
-static ENDPOINT: &'static str = "https://api.openweathermap.org/data/2.5/weather";
+static DEFAULT_ENDPOINT: &str = "https://api.openweathermap.org/data/2.5/weather";
+
+static DEFAULT_ONE_CALL_ENDPOINT: &str = "https://api.openweathermap.org/data/3.0/onecall";
+
+static DEFAULT_GEOCODING_ENDPOINT: &str = "https://api.openweathermap.org/geo/1.0/direct";
+
+fn default_endpoint() -> String {
+    DEFAULT_ENDPOINT.to_string()
+}
+
+fn default_one_call_endpoint() -> String {
+    DEFAULT_ONE_CALL_ENDPOINT.to_string()
+}
+
+fn default_geocoding_endpoint() -> String {
+    DEFAULT_GEOCODING_ENDPOINT.to_string()
+}
 
 /// Expected temperature output units
 #[derive(Deserialize, Debug)]
@@ -21,8 +47,78 @@ enum WeatherUnits {
 struct OpenWeatherMapConfig {
     api_key: String,
     // e.g. "Sydney,AU"
-    location: String,
+    #[serde(default)]
+    location: Option<String>,
+    /// OpenWeatherMap's numeric city ID, e.g. 2147714 for Sydney.
+    #[serde(default)]
+    city_id: Option<u64>,
+    /// Coordinates in decimal degrees. Both must be set together.
+    #[serde(default)]
+    lat: Option<f64>,
+    #[serde(default)]
+    lon: Option<f64>,
     temp_units: WeatherUnits,
+    /// Language for weather descriptions, e.g. "de" or "pt_br".
+    #[serde(default)]
+    lang: Option<String>,
+    /// Fetches current, hourly, daily and alerts in one request from the
+    /// One Call 3.0 API. Needs coordinates and a One Call subscription.
+    #[serde(default)]
+    one_call: bool,
+    /// URL of the current weather endpoint. Override to go through a
+    /// caching proxy or a local mock.
+    #[serde(default = "default_endpoint")]
+    endpoint: String,
+    /// URL of the One Call endpoint.
+    #[serde(default = "default_one_call_endpoint")]
+    one_call_endpoint: String,
+    /// URL of the direct geocoding endpoint, used for location searches.
+    #[serde(default = "default_geocoding_endpoint")]
+    geocoding_endpoint: String,
+}
+
+impl OpenWeatherMapConfig {
+    /// Returns the query parameters identifying the configured location,
+    /// preferring coordinates, then city IDs, then free text.
+    fn location_query(&self) -> Result<Vec<(&'static str, String)>, WeatherError> {
+        match (self.lat, self.lon) {
+            (Some(lat), Some(lon)) => {
+                return Ok(vec![("lat", lat.to_string()), ("lon", lon.to_string())])
+            }
+            (None, None) => {}
+            _ => {
+                return Err(WeatherError::Config(
+                    "OpenWeatherMap needs both lat and lon to be set".to_string(),
+                ))
+            }
+        }
+
+        if self.one_call {
+            return Err(WeatherError::Config(
+                "OpenWeatherMap's One Call API needs lat and lon to be set".to_string(),
+            ));
+        }
+
+        if let Some(city_id) = self.city_id {
+            return Ok(vec![("id", city_id.to_string())]);
+        }
+
+        match &self.location {
+            Some(location) => Ok(vec![("q", location.clone())]),
+            None => Err(WeatherError::Config(
+                "OpenWeatherMap needs one of location, city_id or lat/lon".to_string(),
+            )),
+        }
+    }
+
+    fn units_query(&self) -> Option<(&'static str, &'static str)> {
+        match self.temp_units {
+            // By default
+            WeatherUnits::Kelvin => None,
+            WeatherUnits::Metric => Some(("units", "metric")),
+            WeatherUnits::Fahrenheit => Some(("units", "imperial")),
+        }
+    }
 }
 
 /// Actual location for request
@@ -111,8 +207,9 @@ struct OpenWeatherMapResponse {
     dt: Option<u64>,
     #[serde(default)]
     sys: Option<ResponseWeatherSystem>,
+    /// Seconds east of UTC
     #[serde(default)]
-    timezone: Option<u64>,
+    timezone: Option<i64>,
     #[serde(default)]
     id: Option<u64>,
     #[serde(default)]
@@ -121,68 +218,387 @@ struct OpenWeatherMapResponse {
     cod: Option<u64>,
 }
 
-pub struct OpenWeatherMap;
+/// Current conditions from the One Call API
+#[derive(Deserialize, Debug)]
+struct OneCallCurrent {
+    dt: i64,
+    temp: f64,
+    #[serde(default)]
+    sunrise: Option<i64>,
+    #[serde(default)]
+    sunset: Option<i64>,
+    #[serde(default)]
+    uvi: Option<f64>,
+    weather: Vec<ResponseWeatherEntry>,
+}
 
-impl OpenWeatherMap {}
+/// A single hour from the One Call API
+#[derive(Deserialize, Debug)]
+struct OneCallHourly {
+    dt: i64,
+    temp: f64,
+    #[serde(default)]
+    weather: Vec<ResponseWeatherEntry>,
+}
 
-impl WeatherProvider for OpenWeatherMap {
-    fn get_weather(config: Option<toml::Value>) -> Result<Weather, String> {
-        // We require a config for OpenWeatherMap:
-        let config = config.ok_or_else(|| "OpenWeatherMap configuration needed".to_string())?;
+/// Temperatures throughout a day from the One Call API
+#[derive(Deserialize, Debug)]
+struct OneCallDailyTemp {
+    #[serde(default)]
+    min: Option<f64>,
+    #[serde(default)]
+    max: Option<f64>,
+}
 
-        // Parse into a configuration type
-        let config: OpenWeatherMapConfig = config
-            .try_into()
-            .map_err(|x| format!("Failed to parse OpenWeatherMap config: {:?}", x))?;
+/// A single day from the One Call API
+#[derive(Deserialize, Debug)]
+struct OneCallDaily {
+    dt: i64,
+    #[serde(default)]
+    sunrise: Option<i64>,
+    #[serde(default)]
+    sunset: Option<i64>,
+    temp: OneCallDailyTemp,
+    #[serde(default)]
+    uvi: Option<f64>,
+}
 
-        let client = reqwest::blocking::Client::new();
+/// A government weather alert from the One Call API
+#[derive(Deserialize, Debug)]
+struct OneCallAlert {
+    #[serde(default)]
+    sender_name: String,
+    event: String,
+    start: i64,
+    #[serde(default)]
+    end: Option<i64>,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
 
-        let mut request = client
-            .get(ENDPOINT)
-            .query(&[("APPID", config.api_key), ("q", config.location)]);
+/// JSON output from the One Call API
+#[derive(Deserialize, Debug)]
+struct OneCallResponse {
+    #[serde(default)]
+    timezone_offset: i64,
+    current: OneCallCurrent,
+    #[serde(default)]
+    hourly: Vec<OneCallHourly>,
+    #[serde(default)]
+    daily: Vec<OneCallDaily>,
+    #[serde(default)]
+    alerts: Vec<OneCallAlert>,
+}
 
-        match config.temp_units {
-            WeatherUnits::Kelvin => {
-                // By default
-            }
-            WeatherUnits::Metric => {
-                request = request.query(&[("units", "metric")]);
-            }
-            WeatherUnits::Fahrenheit => {
-                request = request.query(&[("units", "imperial")]);
-            }
+/// A single match from the geocoding API
+#[derive(Deserialize, Debug)]
+struct GeocodingResult {
+    name: String,
+    lat: f64,
+    lon: f64,
+    #[serde(default)]
+    country: Option<String>,
+    #[serde(default)]
+    state: Option<String>,
+}
+
+fn timestamp(secs: i64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(secs, 0).single()
+}
+
+/// Returns the start of the local day containing `secs`, given the location's
+/// offset from UTC.
+fn start_of_day(secs: i64, offset: i64) -> Option<DateTime<Utc>> {
+    let local = timestamp(secs + offset)?;
+    let midnight = Utc.from_utc_datetime(&local.date_naive().and_hms_opt(0, 0, 0)?);
+
+    Some(midnight - Duration::seconds(offset))
+}
+
+/// Converts a language tag like "de-DE" into the code OpenWeatherMap expects.
+pub fn language_code(tag: &str) -> String {
+    let tag = tag.replace('-', "_").to_lowercase();
+
+    match tag.as_str() {
+        // Only these languages are split by region
+        "pt_br" | "zh_cn" | "zh_tw" => tag,
+        _ => tag.split('_').next().unwrap_or_default().to_string(),
+    }
+}
+
+/// Turns OpenWeatherMap's lower case descriptions into a sentence.
+fn describe(entry: &ResponseWeatherEntry) -> String {
+    let description = entry.description.to_sentence_case();
+
+    // Inflector only capitalises ASCII, which misses translations like "überwiegend"
+    let mut chars = description.chars();
+    let mut description = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    };
+
+    if !description.ends_with('.') {
+        description += ".";
+    }
+
+    description
+}
+
+/// Sends a request, classifying any failures.
+fn send<T>(request: reqwest::blocking::RequestBuilder) -> Result<T, WeatherError>
+where
+    for<'de> T: serde::Deserialize<'de>,
+{
+    let response = request
+        .send()
+        .map_err(|x| WeatherError::Transient(format!("Failed to get weather status: {:?}", x)))?;
+
+    response.error_for_status_ref().map_err(|x| {
+        let msg = format!("Got bad status code while getting weather: {:?}", x);
+
+        match x.status().map(|x| x.as_u16()) {
+            Some(401) | Some(403) => WeatherError::Auth(msg),
+            // Unknown location
+            Some(404) => WeatherError::Config(msg),
+            _ => WeatherError::Transient(msg),
         }
+    })?;
+
+    response
+        .json()
+        .map_err(|x| WeatherError::Transient(format!("Failed to parse weather JSON: {:?}", x)))
+}
+
+pub struct OpenWeatherMap;
 
-        let response = request
-            .send()
-            .map_err(|x| format!("Failed to get weather status: {:?}", x))?;
+impl OpenWeatherMap {
+    /// Fetches from the 2.5 current weather API.
+    fn get_current_weather(
+        client: &reqwest::blocking::Client,
+        config: &OpenWeatherMapConfig,
+    ) -> Result<Weather, WeatherError> {
+        let mut request = client
+            .get(&config.endpoint)
+            .query(&[("APPID", &config.api_key)])
+            .query(&config.location_query()?);
 
-        response
-            .error_for_status_ref()
-            .map_err(|x| format!("Got bad status code while getting weather: {:?}", x))?;
+        if let Some(units) = config.units_query() {
+            request = request.query(&[units]);
+        }
+        if let Some(lang) = &config.lang {
+            request = request.query(&[("lang", lang)]);
+        }
 
-        let json: OpenWeatherMapResponse = response
-            .json()
-            .map_err(|x| format!("Failed to parse weather JSON: {:?}", x))?;
+        let json: OpenWeatherMapResponse = send(request)?;
 
         info!("Downloaded weather from OpenWeatherMap successfully");
 
-        let weather_state = json
-            .weather
-            .get(0)
-            .ok_or_else(|| "No weather entry in JSON response".to_string())?;
+        let weather_state = json.weather.first().ok_or_else(|| {
+            WeatherError::Transient("No weather entry in JSON response".to_string())
+        })?;
 
-        let mut description = weather_state.description.to_sentence_case();
+        // The current weather API only reports today's sun times
+        let daily = match json.sys {
+            Some(ref sys) => {
+                // Missing fields are defaulted to 0
+                let sun_time = |secs: u64| match secs {
+                    0 => None,
+                    secs => timestamp(secs as i64),
+                };
 
-        if !description.ends_with(".") {
-            description += ".";
-        }
+                // Days start at local midnight, as with the One Call API
+                let dt = match json.dt {
+                    Some(dt) if dt != 0 => dt as i64,
+                    _ => Utc::now().timestamp(),
+                };
+                let date = start_of_day(dt, json.timezone.unwrap_or(0)).unwrap_or_else(Utc::now);
+
+                vec![Forecast {
+                    date,
+                    temp_min: None,
+                    temp_max: None,
+                    sunrise: sun_time(sys.sunrise),
+                    sunset: sun_time(sys.sunset),
+                    uv: None,
+                }]
+            }
+            None => Vec::new(),
+        };
 
         let weather = Weather {
             temperature: json.main.temp,
-            description: description,
+            description: describe(weather_state),
+            daily,
+            hourly: Vec::new(),
+            warnings: None,
         };
 
         Ok(weather)
     }
+
+    /// Fetches from the One Call 3.0 API.
+    fn get_one_call(
+        client: &reqwest::blocking::Client,
+        config: &OpenWeatherMapConfig,
+    ) -> Result<Weather, WeatherError> {
+        let mut request = client
+            .get(&config.one_call_endpoint)
+            .query(&[("appid", &config.api_key)])
+            .query(&config.location_query()?)
+            .query(&[("exclude", "minutely")]);
+
+        if let Some(units) = config.units_query() {
+            request = request.query(&[units]);
+        }
+        if let Some(lang) = &config.lang {
+            request = request.query(&[("lang", lang)]);
+        }
+
+        let json: OneCallResponse = send(request)?;
+
+        info!("Downloaded weather from OpenWeatherMap One Call successfully");
+
+        let weather_state = json.current.weather.first().ok_or_else(|| {
+            WeatherError::Transient("No weather entry in JSON response".to_string())
+        })?;
+
+        let offset = json.timezone_offset;
+
+        let mut daily: Vec<Forecast> = json
+            .daily
+            .iter()
+            .filter_map(|day| {
+                Some(Forecast {
+                    date: start_of_day(day.dt, offset)?,
+                    temp_min: day.temp.min,
+                    temp_max: day.temp.max,
+                    sunrise: day.sunrise.and_then(timestamp),
+                    sunset: day.sunset.and_then(timestamp),
+                    uv: day.uvi.map(|max_index| UvIndex {
+                        max_index,
+                        category: UvCategory::from_index(max_index),
+                    }),
+                })
+            })
+            .collect();
+
+        // Fall back to today's values from the current conditions
+        if daily.is_empty() {
+            if let Some(date) = start_of_day(json.current.dt, offset) {
+                daily.push(Forecast {
+                    date,
+                    temp_min: None,
+                    temp_max: None,
+                    sunrise: json.current.sunrise.and_then(timestamp),
+                    sunset: json.current.sunset.and_then(timestamp),
+                    uv: json.current.uvi.map(|max_index| UvIndex {
+                        max_index,
+                        category: UvCategory::from_index(max_index),
+                    }),
+                });
+            }
+        }
+
+        let hourly = json
+            .hourly
+            .iter()
+            .filter_map(|hour| {
+                Some(HourlyForecast {
+                    time: timestamp(hour.dt)?,
+                    temperature: hour.temp,
+                    description: hour.weather.first().map(describe),
+                })
+            })
+            .collect();
+
+        let warnings = json
+            .alerts
+            .into_iter()
+            .map(|alert| {
+                // Alerts don't carry a severity, so go by the wording agencies use
+                let text = format!("{} {}", alert.event, alert.tags.join(" ")).to_lowercase();
+                let severity = if text.contains("severe") || text.contains("extreme") {
+                    WarningSeverity::Major
+                } else {
+                    WarningSeverity::Minor
+                };
+
+                WeatherWarning {
+                    id: format!("{}:{}:{}", alert.sender_name, alert.event, alert.start),
+                    title: alert.event,
+                    severity,
+                    expiry_time: alert.end.and_then(timestamp),
+                }
+            })
+            .collect();
+
+        Ok(Weather {
+            temperature: json.current.temp,
+            description: describe(weather_state),
+            daily,
+            hourly,
+            warnings: Some(warnings),
+        })
+    }
+}
+
+impl WeatherProvider for OpenWeatherMap {
+    fn get_weather(config: Option<toml::Value>) -> Result<Weather, WeatherError> {
+        // We require a config for OpenWeatherMap:
+        let config = config.ok_or_else(|| {
+            WeatherError::Config("OpenWeatherMap configuration needed".to_string())
+        })?;
+
+        // Parse into a configuration type
+        let config: OpenWeatherMapConfig = config.try_into().map_err(|x| {
+            WeatherError::Config(format!("Failed to parse OpenWeatherMap config: {:?}", x))
+        })?;
+
+        let client = reqwest::blocking::Client::new();
+
+        if config.one_call {
+            Self::get_one_call(&client, &config)
+        } else {
+            Self::get_current_weather(&client, &config)
+        }
+    }
+
+    fn search_locations(
+        config: Option<toml::Value>,
+        text: &str,
+    ) -> Result<Vec<LocationMatch>, WeatherError> {
+        // The API key is needed, so this requires a config just like fetching weather
+        let config = config.ok_or_else(|| {
+            WeatherError::Config("OpenWeatherMap configuration needed".to_string())
+        })?;
+
+        let config: OpenWeatherMapConfig = config.try_into().map_err(|x| {
+            WeatherError::Config(format!("Failed to parse OpenWeatherMap config: {:?}", x))
+        })?;
+
+        let client = reqwest::blocking::Client::new();
+
+        let request = client.get(&config.geocoding_endpoint).query(&[
+            ("appid", config.api_key.as_str()),
+            ("q", text),
+            ("limit", "5"),
+        ]);
+
+        let results: Vec<GeocodingResult> = send(request)?;
+
+        Ok(results
+            .into_iter()
+            .map(|x| LocationMatch {
+                name: x.name,
+                region: x.state,
+                postcode: None,
+                country: x.country,
+                geohash: None,
+                lat: Some(x.lat),
+                lon: Some(x.lon),
+            })
+            .collect())
+    }
 }
@@ -0,0 +1,397 @@
+extern crate leaffront_weather;
+extern crate toml;
+
+mod common;
+
+use common::{fixture, MockResponse, MockServer};
+
+use leaffront_weather::bom::BOM;
+use leaffront_weather::{UvCategory, WarningSeverity, WeatherError, WeatherProvider};
+
+fn config(server: &MockServer, location: &str) -> Option<toml::Value> {
+    let mut table = toml::value::Table::new();
+    table.insert("location".into(), toml::Value::String(location.into()));
+    table.insert("endpoint".into(), toml::Value::String(server.url().into()));
+    Some(toml::Value::Table(table))
+}
+
+#[test]
+fn fetches_weather() {
+    let server = MockServer::start(vec![
+        (
+            "/locations?search=Sydney",
+            MockResponse::ok(fixture("bom/locations_sydney.json")),
+        ),
+        (
+            "/locations/r3gx2f9/forecasts/daily",
+            MockResponse::ok(fixture("bom/forecasts_daily.json")),
+        ),
+        (
+            "/locations/r3gx2f9/observations",
+            MockResponse::ok(fixture("bom/observations.json")),
+        ),
+        (
+            "/locations/r3gx2f9/warnings",
+            MockResponse::ok(fixture("bom/warnings_none.json")),
+        ),
+    ]);
+
+    let weather = BOM::get_weather(config(&server, "Sydney")).expect("Weather should parse");
+
+    assert_eq!(weather.temperature, 22.4);
+    assert_eq!(weather.description, "Sunny.");
+    assert_eq!(weather.warnings, Some(Vec::new()));
+
+    assert_eq!(weather.daily.len(), 2);
+    let today = &weather.daily[0];
+    assert_eq!(today.date.to_rfc3339(), "2024-09-13T14:00:00+00:00");
+    assert_eq!(today.temp_max, Some(24.0));
+    assert_eq!(today.temp_min, None);
+    assert_eq!(
+        today.sunrise.map(|x| x.to_rfc3339()).as_deref(),
+        Some("2024-09-13T19:59:00+00:00")
+    );
+    assert_eq!(
+        today.sunset.map(|x| x.to_rfc3339()).as_deref(),
+        Some("2024-09-14T07:43:00+00:00")
+    );
+    let uv = today.uv.expect("UV should be present");
+    assert_eq!(uv.max_index, 5.0);
+    assert_eq!(uv.category, UvCategory::Moderate);
+    assert_eq!(
+        weather.daily[1].uv.map(|x| x.category),
+        Some(UvCategory::High)
+    );
+}
+
+#[test]
+fn fetches_active_warnings() {
+    let server = MockServer::start(vec![
+        (
+            "/locations?search=Sydney",
+            MockResponse::ok(fixture("bom/locations_sydney.json")),
+        ),
+        (
+            "/locations/r3gx2f9/forecasts/daily",
+            MockResponse::ok(fixture("bom/forecasts_daily.json")),
+        ),
+        (
+            "/locations/r3gx2f9/observations",
+            MockResponse::ok(fixture("bom/observations.json")),
+        ),
+        (
+            "/locations/r3gx2f9/warnings",
+            MockResponse::ok(fixture("bom/warnings.json")),
+        ),
+    ]);
+
+    let weather = BOM::get_weather(config(&server, "Sydney")).expect("Weather should parse");
+    let warnings = weather.warnings.expect("Warnings should be present");
+
+    // The cancelled gale warning should be dropped
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].id, "NSW_FL049_IDN36503");
+    assert_eq!(warnings[0].severity, WarningSeverity::Minor);
+    assert_eq!(
+        warnings[1].title,
+        "Severe Thunderstorm Warning for Metropolitan"
+    );
+    assert_eq!(warnings[1].severity, WarningSeverity::Major);
+    assert_eq!(
+        warnings[1].expiry_time.map(|x| x.to_rfc3339()),
+        Some("2024-09-14T06:40:00+00:00".to_string())
+    );
+}
+
+#[test]
+fn tolerates_missing_warnings() {
+    let server = MockServer::start(vec![
+        (
+            "/locations?search=Sydney",
+            MockResponse::ok(fixture("bom/locations_sydney.json")),
+        ),
+        (
+            "/locations/r3gx2f9/forecasts/daily",
+            MockResponse::ok(fixture("bom/forecasts_daily.json")),
+        ),
+        (
+            "/locations/r3gx2f9/observations",
+            MockResponse::ok(fixture("bom/observations.json")),
+        ),
+    ]);
+
+    let weather = BOM::get_weather(config(&server, "Sydney")).expect("Weather should parse");
+
+    assert_eq!(weather.temperature, 22.4);
+    assert_eq!(weather.warnings, None);
+}
+
+#[test]
+fn retries_with_shorter_geohash() {
+    // BOM only carries forecasts for some geohash precisions - the provider
+    // should progressively trim the geohash until something parses.
+    let server = MockServer::start(vec![
+        (
+            "/locations?search=Sydney",
+            MockResponse::ok(fixture("bom/locations_sydney.json")),
+        ),
+        (
+            "/locations/r3gx2f9/forecasts/daily",
+            MockResponse::status(400, fixture("bom/not_found.json")),
+        ),
+        (
+            "/locations/r3gx2f/forecasts/daily",
+            MockResponse::ok(fixture("bom/forecasts_daily.json")),
+        ),
+        (
+            "/locations/r3gx2f9/observations",
+            MockResponse::ok(fixture("bom/observations.json")),
+        ),
+        (
+            "/locations/r3gx2f9/warnings",
+            MockResponse::ok(fixture("bom/warnings_none.json")),
+        ),
+    ]);
+
+    let weather = BOM::get_weather(config(&server, "Sydney")).expect("Weather should parse");
+
+    assert_eq!(weather.description, "Sunny.");
+    assert_eq!(
+        server.requests(),
+        vec![
+            "/locations?search=Sydney",
+            "/locations/r3gx2f9/forecasts/daily",
+            "/locations/r3gx2f/forecasts/daily",
+            "/locations/r3gx2f9/observations",
+            "/locations/r3gx2f9/warnings",
+        ]
+    );
+}
+
+#[test]
+fn gives_up_at_minimum_geohash_length() {
+    let server = MockServer::start(vec![(
+        "/locations?search=Sydney",
+        MockResponse::ok(fixture("bom/locations_sydney.json")),
+    )]);
+
+    let result = BOM::get_weather(config(&server, "Sydney"));
+
+    assert!(result.is_err());
+    assert_eq!(
+        server.requests(),
+        vec![
+            "/locations?search=Sydney",
+            "/locations/r3gx2f9/forecasts/daily",
+            "/locations/r3gx2f/forecasts/daily",
+            "/locations/r3gx2/forecasts/daily",
+            "/locations/r3gx/forecasts/daily",
+        ]
+    );
+}
+
+#[test]
+fn rejects_malformed_json() {
+    let server = MockServer::start(vec![(
+        "/locations?search=Sydney",
+        MockResponse::ok("{\"data\": [".to_string()),
+    )]);
+
+    let error = BOM::get_weather(config(&server, "Sydney")).unwrap_err();
+
+    assert!(!error.is_permanent());
+    let error = error.to_string();
+    assert!(
+        error.contains("Failed to parse BOM weather response"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn reports_http_errors() {
+    let server = MockServer::start(vec![(
+        "/locations?search=Sydney",
+        MockResponse::status(500, "Internal Server Error".to_string()),
+    )]);
+
+    let error = BOM::get_weather(config(&server, "Sydney")).unwrap_err();
+
+    assert!(!error.is_permanent());
+}
+
+#[test]
+fn reports_unknown_location() {
+    let server = MockServer::start(vec![(
+        "/locations?search=Atlantis",
+        MockResponse::ok("{\"metadata\": {}, \"data\": []}".to_string()),
+    )]);
+
+    let error = BOM::get_weather(config(&server, "Atlantis")).unwrap_err();
+
+    match error {
+        WeatherError::Config(msg) => assert!(msg.contains("No response for location"), "{}", msg),
+        e => panic!("Expected a configuration error, got {:?}", e),
+    }
+}
+
+/// Routes for a successful forecast of the given geohash.
+fn forecast_routes(geohash: &str) -> Vec<(String, MockResponse)> {
+    vec![
+        (
+            format!("/locations/{}/forecasts/daily", geohash),
+            MockResponse::ok(fixture("bom/forecasts_daily.json")),
+        ),
+        (
+            format!("/locations/{}/observations", geohash),
+            MockResponse::ok(fixture("bom/observations.json")),
+        ),
+        (
+            format!("/locations/{}/warnings", geohash),
+            MockResponse::ok(fixture("bom/warnings_none.json")),
+        ),
+    ]
+}
+
+fn start_server(extra: Vec<(String, MockResponse)>) -> MockServer {
+    MockServer::start(
+        extra
+            .iter()
+            .map(|(path, response)| (path.as_str(), response.clone()))
+            .collect(),
+    )
+}
+
+#[test]
+fn filters_locations_by_postcode() {
+    let mut routes = forecast_routes("r3gx2u6");
+    routes.push((
+        "/locations?search=Sydney".to_string(),
+        MockResponse::ok(fixture("bom/locations_sydney.json")),
+    ));
+    let server = start_server(routes);
+
+    let mut config = config(&server, "Sydney").unwrap();
+    config
+        .as_table_mut()
+        .unwrap()
+        .insert("postcode".into(), toml::Value::String("2127".into()));
+
+    assert!(BOM::get_weather(Some(config)).is_ok());
+    assert_eq!(
+        server.requests()[1],
+        "/locations/r3gx2u6/forecasts/daily".to_string()
+    );
+}
+
+#[test]
+fn searches_by_postcode_alone() {
+    let mut routes = forecast_routes("r3gx2f9");
+    routes.push((
+        "/locations?search=2000".to_string(),
+        MockResponse::ok(fixture("bom/locations_sydney.json")),
+    ));
+    let server = start_server(routes);
+
+    let mut table = toml::value::Table::new();
+    table.insert("postcode".into(), toml::Value::String("2000".into()));
+    table.insert("endpoint".into(), toml::Value::String(server.url().into()));
+
+    assert!(BOM::get_weather(Some(toml::Value::Table(table))).is_ok());
+    assert_eq!(server.requests()[0], "/locations?search=2000".to_string());
+}
+
+#[test]
+fn rejects_unmatched_state() {
+    let server = MockServer::start(vec![(
+        "/locations?search=Sydney",
+        MockResponse::ok(fixture("bom/locations_sydney.json")),
+    )]);
+
+    let mut config = config(&server, "Sydney").unwrap();
+    config
+        .as_table_mut()
+        .unwrap()
+        .insert("state".into(), toml::Value::String("vic".into()));
+
+    match BOM::get_weather(Some(config)) {
+        Err(WeatherError::Config(msg)) => {
+            assert!(msg.contains("Sydney Olympic Park, NSW 2127"), "{}", msg)
+        }
+        e => panic!("Expected a configuration error, got {:?}", e),
+    }
+}
+
+#[test]
+fn uses_explicit_geohash() {
+    let server = start_server(forecast_routes("r3gx2u6"));
+
+    let mut table = toml::value::Table::new();
+    table.insert("geohash".into(), toml::Value::String("r3gx2u6".into()));
+    table.insert("endpoint".into(), toml::Value::String(server.url().into()));
+
+    assert!(BOM::get_weather(Some(toml::Value::Table(table))).is_ok());
+    assert!(server
+        .requests()
+        .iter()
+        .all(|x| x.starts_with("/locations/r3gx2u6/")));
+}
+
+#[test]
+fn caches_resolved_location() {
+    let mut routes = forecast_routes("r3gx2f9");
+    routes.push((
+        "/locations?search=Sydney".to_string(),
+        MockResponse::ok(fixture("bom/locations_sydney.json")),
+    ));
+    let server = start_server(routes);
+
+    assert!(BOM::get_weather(config(&server, "Sydney")).is_ok());
+    assert!(BOM::get_weather(config(&server, "Sydney")).is_ok());
+
+    assert_eq!(
+        server
+            .requests()
+            .iter()
+            .filter(|x| x.starts_with("/locations?"))
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn requires_location() {
+    let server = MockServer::start(vec![]);
+
+    let mut table = toml::value::Table::new();
+    table.insert("state".into(), toml::Value::String("NSW".into()));
+    table.insert("endpoint".into(), toml::Value::String(server.url().into()));
+
+    match BOM::get_weather(Some(toml::Value::Table(table))) {
+        Err(WeatherError::Config(_)) => {}
+        e => panic!("Expected a configuration error, got {:?}", e),
+    }
+}
+
+#[test]
+fn searches_locations() {
+    let server = MockServer::start(vec![(
+        "/locations?search=Sydney",
+        MockResponse::ok(fixture("bom/locations_sydney.json")),
+    )]);
+
+    let matches =
+        BOM::search_locations(config(&server, "ignored"), "Sydney").expect("Search should succeed");
+
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[1].geohash.as_deref(), Some("r3gx2u6"));
+    assert_eq!(matches[0].to_string(), "Sydney, NSW 2000 (geohash r3gx2f9)");
+}
+
+#[test]
+fn requires_config() {
+    match BOM::get_weather(None) {
+        Err(WeatherError::Config(_)) => {}
+        e => panic!("Expected a configuration error, got {:?}", e),
+    }
+}
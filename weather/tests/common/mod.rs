@@ -0,0 +1,126 @@
+//! A tiny HTTP stand-in for weather provider APIs, serving recorded responses.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A canned response for a single path.
+#[derive(Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+impl MockResponse {
+    pub fn ok(body: String) -> Self {
+        MockResponse { status: 200, body }
+    }
+
+    pub fn status(status: u16, body: String) -> Self {
+        MockResponse { status, body }
+    }
+}
+
+/// Reads a recorded response from `tests/fixtures`.
+pub fn fixture(name: &str) -> String {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests");
+    path.push("fixtures");
+    path.push(name);
+
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("Failed to read {:?}: {:?}", path, e))
+}
+
+/// Serves canned responses keyed on request path (including query string).
+/// Any unknown path gets a 404.
+pub struct MockServer {
+    address: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    pub fn start(routes: Vec<(&str, MockResponse)>) -> Self {
+        let routes: HashMap<String, MockResponse> = routes
+            .into_iter()
+            .map(|(path, response)| (path.to_string(), response))
+            .collect();
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind mock server");
+        let address = format!("http://{}", listener.local_addr().unwrap());
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let thread_requests = requests.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => break,
+                };
+
+                handle(stream, &routes, &thread_requests);
+            }
+        });
+
+        MockServer { address, requests }
+    }
+
+    /// The base URL of this server, e.g. "http://127.0.0.1:1234".
+    pub fn url(&self) -> &str {
+        &self.address
+    }
+
+    /// Every path requested so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn handle(
+    mut stream: TcpStream,
+    routes: &HashMap<String, MockResponse>,
+    requests: &Mutex<Vec<String>>,
+) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+
+    // Drain headers - we only ever serve GETs, so there is no body.
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) if line == "\r\n" => break,
+            Ok(_) => {}
+            Err(_) => return,
+        }
+    }
+
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("")
+        .to_string();
+    requests.lock().unwrap().push(path.clone());
+
+    let response = routes
+        .get(&path)
+        .cloned()
+        .unwrap_or_else(|| MockResponse::status(404, "Not Found".to_string()));
+
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.body.len(),
+        response.body
+    );
+}
@@ -0,0 +1,310 @@
+extern crate leaffront_weather;
+extern crate toml;
+
+mod common;
+
+use common::{fixture, MockResponse, MockServer};
+
+use leaffront_weather::openweathermap::{language_code, OpenWeatherMap};
+use leaffront_weather::{
+    UvCategory, WarningSeverity, WeatherError, WeatherProvider, WeatherProviderConfig,
+    WeatherProviderKind,
+};
+
+fn config(server: &MockServer, temp_units: &str) -> Option<toml::Value> {
+    let mut table = toml::value::Table::new();
+    table.insert("api_key".into(), toml::Value::String("test-key".into()));
+    table.insert("location".into(), toml::Value::String("Sydney,AU".into()));
+    table.insert("temp_units".into(), toml::Value::String(temp_units.into()));
+    table.insert(
+        "endpoint".into(),
+        toml::Value::String(format!("{}/data/2.5/weather", server.url())),
+    );
+    Some(toml::Value::Table(table))
+}
+
+#[test]
+fn fetches_weather() {
+    let server = MockServer::start(vec![(
+        "/data/2.5/weather?APPID=test-key&q=Sydney%2CAU&units=metric",
+        MockResponse::ok(fixture("openweathermap/weather_sydney.json")),
+    )]);
+
+    let weather =
+        OpenWeatherMap::get_weather(config(&server, "Metric")).expect("Weather should parse");
+
+    assert_eq!(weather.temperature, 22.31);
+    assert_eq!(weather.description, "Few clouds.");
+    assert_eq!(weather.warnings, None);
+
+    assert_eq!(weather.daily.len(), 1);
+    let today = &weather.daily[0];
+    assert_eq!(today.date.to_rfc3339(), "2024-09-13T14:00:00+00:00");
+    assert_eq!(today.sunrise.map(|x| x.timestamp()), Some(1726257540));
+    assert_eq!(today.sunset.map(|x| x.timestamp()), Some(1726299780));
+    assert_eq!(today.uv, None);
+}
+
+#[test]
+fn dates_weather_west_of_utc() {
+    let server = MockServer::start(vec![(
+        "/data/2.5/weather?APPID=test-key&q=Sydney%2CAU&units=metric",
+        MockResponse::ok(fixture("openweathermap/weather_new_york.json")),
+    )]);
+
+    let weather =
+        OpenWeatherMap::get_weather(config(&server, "Metric")).expect("Weather should parse");
+
+    // Still the evening of the 13th in New York
+    let today = &weather.daily[0];
+    assert_eq!(today.date.to_rfc3339(), "2024-09-13T04:00:00+00:00");
+    assert_eq!(today.sunrise.map(|x| x.timestamp()), Some(1726223940));
+    assert_eq!(today.sunset.map(|x| x.timestamp()), Some(1726269000));
+}
+
+#[test]
+fn fetches_translated_weather() {
+    let body =
+        fixture("openweathermap/weather_sydney.json").replace("few clouds", "überwiegend bewölkt");
+    let server = MockServer::start(vec![(
+        "/data/2.5/weather?APPID=test-key&q=Sydney%2CAU&units=metric&lang=de",
+        MockResponse::ok(body),
+    )]);
+
+    let mut provider = WeatherProviderConfig {
+        kind: WeatherProviderKind::OpenWeatherMap,
+        config: config(&server, "Metric"),
+    };
+    provider.set_language("de-DE");
+
+    let weather = provider.get_weather().expect("Weather should parse");
+    assert_eq!(weather.description, "Überwiegend bewölkt.");
+
+    assert_eq!(language_code("pt-BR"), "pt_br");
+    assert_eq!(language_code("fr_CA"), "fr");
+}
+
+#[test]
+fn omits_units_for_kelvin() {
+    let server = MockServer::start(vec![(
+        "/data/2.5/weather?APPID=test-key&q=Sydney%2CAU",
+        MockResponse::ok(fixture("openweathermap/weather_sydney.json")),
+    )]);
+
+    assert!(OpenWeatherMap::get_weather(config(&server, "Kelvin")).is_ok());
+}
+
+#[test]
+fn reports_auth_errors() {
+    let server = MockServer::start(vec![(
+        "/data/2.5/weather?APPID=test-key&q=Sydney%2CAU&units=metric",
+        MockResponse::status(401, fixture("openweathermap/invalid_api_key.json")),
+    )]);
+
+    match OpenWeatherMap::get_weather(config(&server, "Metric")) {
+        Err(WeatherError::Auth(msg)) => assert!(msg.contains("bad status code"), "{}", msg),
+        e => panic!("Expected an authentication error, got {:?}", e),
+    }
+}
+
+#[test]
+fn reports_unknown_location() {
+    let server = MockServer::start(vec![]);
+
+    match OpenWeatherMap::get_weather(config(&server, "Metric")) {
+        Err(WeatherError::Config(_)) => {}
+        e => panic!("Expected a configuration error, got {:?}", e),
+    }
+}
+
+#[test]
+fn reports_server_errors() {
+    let server = MockServer::start(vec![(
+        "/data/2.5/weather?APPID=test-key&q=Sydney%2CAU&units=metric",
+        MockResponse::status(502, "Bad Gateway".to_string()),
+    )]);
+
+    let error = OpenWeatherMap::get_weather(config(&server, "Metric")).unwrap_err();
+
+    assert_eq!(
+        error,
+        WeatherError::Transient(error.to_string()),
+        "Expected a transient error"
+    );
+}
+
+#[test]
+fn rejects_malformed_json() {
+    let server = MockServer::start(vec![(
+        "/data/2.5/weather?APPID=test-key&q=Sydney%2CAU&units=metric",
+        MockResponse::ok("{\"weather\": \"sunny\"}".to_string()),
+    )]);
+
+    let error = OpenWeatherMap::get_weather(config(&server, "Metric")).unwrap_err();
+
+    assert!(!error.is_permanent());
+    let error = error.to_string();
+    assert!(error.contains("Failed to parse weather JSON"), "{}", error);
+}
+
+/// Config with some location fields replaced.
+fn config_with(server: &MockServer, fields: Vec<(&str, toml::Value)>) -> Option<toml::Value> {
+    let mut config = config(server, "Metric").unwrap();
+    let table = config.as_table_mut().unwrap();
+    table.remove("location");
+    table.insert(
+        "one_call_endpoint".into(),
+        toml::Value::String(format!("{}/data/3.0/onecall", server.url())),
+    );
+
+    for (key, value) in fields {
+        table.insert(key.into(), value);
+    }
+
+    Some(config)
+}
+
+#[test]
+fn fetches_by_city_id() {
+    let server = MockServer::start(vec![(
+        "/data/2.5/weather?APPID=test-key&id=2147714&units=metric",
+        MockResponse::ok(fixture("openweathermap/weather_sydney.json")),
+    )]);
+
+    let config = config_with(&server, vec![("city_id", toml::Value::Integer(2147714))]);
+
+    assert!(OpenWeatherMap::get_weather(config).is_ok());
+}
+
+#[test]
+fn fetches_by_coordinates() {
+    let server = MockServer::start(vec![(
+        "/data/2.5/weather?APPID=test-key&lat=-33.8679&lon=151.2073&units=metric",
+        MockResponse::ok(fixture("openweathermap/weather_sydney.json")),
+    )]);
+
+    let config = config_with(
+        &server,
+        vec![
+            ("lat", toml::Value::Float(-33.8679)),
+            ("lon", toml::Value::Float(151.2073)),
+            // Coordinates take priority
+            ("city_id", toml::Value::Integer(2147714)),
+        ],
+    );
+
+    assert!(OpenWeatherMap::get_weather(config).is_ok());
+}
+
+#[test]
+fn fetches_one_call() {
+    let server = MockServer::start(vec![(
+        "/data/3.0/onecall?appid=test-key&lat=-33.8679&lon=151.2073&exclude=minutely&units=metric",
+        MockResponse::ok(fixture("openweathermap/onecall_sydney.json")),
+    )]);
+
+    let config = config_with(
+        &server,
+        vec![
+            ("lat", toml::Value::Float(-33.8679)),
+            ("lon", toml::Value::Float(151.2073)),
+            ("one_call", toml::Value::Boolean(true)),
+        ],
+    );
+
+    let weather = OpenWeatherMap::get_weather(config).expect("Weather should parse");
+
+    assert_eq!(weather.temperature, 22.31);
+    assert_eq!(weather.description, "Few clouds.");
+
+    assert_eq!(weather.hourly.len(), 2);
+    assert_eq!(weather.hourly[1].temperature, 23.4);
+    assert_eq!(
+        weather.hourly[1].description.as_deref(),
+        Some("Light rain.")
+    );
+
+    assert_eq!(weather.daily.len(), 2);
+    let today = &weather.daily[0];
+    // Local midnight in Sydney
+    assert_eq!(today.date.to_rfc3339(), "2024-09-13T14:00:00+00:00");
+    assert_eq!(today.temp_min, Some(14.2));
+    assert_eq!(today.temp_max, Some(24.1));
+    assert_eq!(today.sunrise.map(|x| x.timestamp()), Some(1726257540));
+    assert_eq!(
+        weather.daily[1].uv.map(|x| x.category),
+        Some(UvCategory::VeryHigh)
+    );
+
+    let warnings = weather.warnings.expect("Alerts should be present");
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(warnings[0].title, "Severe Thunderstorm Warning");
+    assert_eq!(warnings[0].severity, WarningSeverity::Major);
+    assert_eq!(
+        warnings[0].expiry_time.map(|x| x.to_rfc3339()),
+        Some("2024-09-14T08:00:00+00:00".to_string())
+    );
+    assert_eq!(warnings[1].severity, WarningSeverity::Minor);
+}
+
+#[test]
+fn one_call_requires_coordinates() {
+    let server = MockServer::start(vec![]);
+
+    let config = config_with(
+        &server,
+        vec![
+            ("city_id", toml::Value::Integer(2147714)),
+            ("one_call", toml::Value::Boolean(true)),
+        ],
+    );
+
+    match OpenWeatherMap::get_weather(config) {
+        Err(WeatherError::Config(_)) => {}
+        e => panic!("Expected a configuration error, got {:?}", e),
+    }
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn rejects_partial_coordinates() {
+    let server = MockServer::start(vec![]);
+
+    let config = config_with(&server, vec![("lat", toml::Value::Float(-33.8679))]);
+
+    match OpenWeatherMap::get_weather(config) {
+        Err(WeatherError::Config(_)) => {}
+        e => panic!("Expected a configuration error, got {:?}", e),
+    }
+}
+
+#[test]
+fn searches_locations() {
+    let server = MockServer::start(vec![(
+        "/geo/1.0/direct?appid=test-key&q=Sydney&limit=5",
+        MockResponse::ok(fixture("openweathermap/geocoding_sydney.json")),
+    )]);
+
+    let mut config = config(&server, "Metric").unwrap();
+    config.as_table_mut().unwrap().insert(
+        "geocoding_endpoint".into(),
+        toml::Value::String(format!("{}/geo/1.0/direct", server.url())),
+    );
+
+    let matches =
+        OpenWeatherMap::search_locations(Some(config), "Sydney").expect("Search should succeed");
+
+    assert_eq!(matches.len(), 2);
+    assert_eq!(
+        matches[1].to_string(),
+        "Sydney, Nova Scotia CA (lat 46.1367899, lon -60.1942209)"
+    );
+}
+
+#[test]
+fn requires_config() {
+    match OpenWeatherMap::get_weather(None) {
+        Err(WeatherError::Config(_)) => {}
+        e => panic!("Expected a configuration error, got {:?}", e),
+    }
+}